@@ -0,0 +1,155 @@
+use core::cmp::Ordering;
+use core::fmt;
+
+use sha2::{Digest, Sha256};
+
+use crate::{Data, Hash, MerkleTree, ProvenLeaf};
+
+/// A proof that some data is not a leaf of a tree whose leaves are sorted in strictly
+/// ascending order
+///
+/// Holds the two adjacent leaves that the data would sort between. At the ends of the tree
+/// only one of them exists, which must then be the first or the last leaf. Adjacency is read
+/// off the proof paths, not the bundled indices, so it holds for any tree shape.
+pub struct AbsenceProof<D = Sha256> {
+    /// The greatest leaf smaller than the data, if any
+    pub left: Option<ProvenLeaf<D>>,
+    /// The smallest leaf greater than the data, if any
+    pub right: Option<ProvenLeaf<D>>,
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Generates a proof that the given data is not a leaf of this tree, whose leaves must be
+    /// sorted in strictly ascending order and retained, see [`MerkleTree::construct_retaining`]
+    /// Returns `None` if the data is a leaf or a leaf it is compared against keeps no data
+    pub fn prove_absence(&self, data: &Data) -> Option<AbsenceProof<D>> {
+        // Binary search for the first leaf greater than the data
+        let (mut low, mut high) = (0, self.leaf_count());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.get_leaf(mid)?.cmp(data) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Equal => return None,
+                Ordering::Greater => high = mid,
+            }
+        }
+
+        Some(AbsenceProof {
+            left: low.checked_sub(1).and_then(|index| self.prove_data(index)),
+            right: self.prove_data(low),
+        })
+    }
+}
+
+impl<D: Digest> AbsenceProof<D> {
+    /// Verifies that the given data is not a leaf of the sorted tree with the given root hash
+    pub fn verify(&self, data: &Data, root_hash: &Hash) -> bool {
+        let bracket = |leaf: &ProvenLeaf<D>, order: Ordering| {
+            leaf.verify(root_hash)
+                && leaf
+                    .data
+                    .as_ref()
+                    .is_some_and(|leaf| leaf.cmp(data) == order)
+        };
+        if !self.left.iter().all(|leaf| bracket(leaf, Ordering::Less))
+            || !self
+                .right
+                .iter()
+                .all(|leaf| bracket(leaf, Ordering::Greater))
+        {
+            return false;
+        }
+
+        // Paths run from the leaf up; the last leaf of a subtree only ever turns right and the
+        // first one only left
+        let directions = |leaf: &ProvenLeaf<D>| leaf.proof.directions();
+        match (&self.left, &self.right) {
+            (Some(left), Some(right)) => adjacent(&directions(left), &directions(right)),
+            (Some(left), None) => directions(left).iter().all(|&is_right| is_right),
+            (None, Some(right)) => directions(right).iter().all(|&is_right| !is_right),
+            (None, None) => false,
+        }
+    }
+}
+
+/// Whether the leaf with the first path immediately precedes the leaf with the second one
+/// Both paths must lead to the same node, the left one as the last leaf of its left subtree
+/// and the right one as the first leaf of its right subtree
+fn adjacent(left: &[bool], right: &[bool]) -> bool {
+    let left_turn = left.iter().take_while(|&&is_right| is_right).count();
+    let right_turn = right.iter().take_while(|&&is_right| !is_right).count();
+    left.get(left_turn) == Some(&false)
+        && right.get(right_turn) == Some(&true)
+        && left[left_turn + 1..] == right[right_turn + 1..]
+}
+
+impl<D> fmt::Debug for AbsenceProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AbsenceProof")
+            .field("left", &self.left)
+            .field("right", &self.right)
+            .finish()
+    }
+}
+
+impl<D> Clone for AbsenceProof<D> {
+    fn clone(&self) -> Self {
+        AbsenceProof {
+            left: self.left.clone(),
+            right: self.right.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Sha256Tree;
+
+    #[test]
+    fn absent_between_leaves() {
+        let input: Vec<_> = (0..8).map(|i| vec![i * 10]).collect();
+        let mt = Sha256Tree::construct_retaining(&input);
+
+        let proof = mt.prove_absence(&vec![35]).unwrap();
+        assert_eq!(proof.left.as_ref().unwrap().index, 3);
+        assert_eq!(proof.right.as_ref().unwrap().index, 4);
+        assert!(proof.verify(&vec![35], mt.root()));
+        assert!(!proof.verify(&vec![45], mt.root()));
+        assert!(!proof.verify(&vec![30], mt.root()));
+
+        assert!(mt.prove_absence(&vec![40]).is_none());
+        assert!(Sha256Tree::construct(&input)
+            .prove_absence(&vec![35])
+            .is_none());
+
+        // Leaves that are not adjacent do not bracket anything
+        let mut gap = proof.clone();
+        gap.right = mt.prove_data(5);
+        assert!(!gap.verify(&vec![35], mt.root()));
+    }
+
+    #[test]
+    fn absent_past_the_ends() {
+        let input: Vec<_> = (1..8).map(|i| vec![i * 10]).collect();
+        let mt = Sha256Tree::construct_retaining(&input);
+
+        let first = mt.prove_absence(&vec![5]).unwrap();
+        assert!(first.left.is_none());
+        assert!(first.verify(&vec![5], mt.root()));
+
+        let last = mt.prove_absence(&vec![75]).unwrap();
+        assert!(last.right.is_none());
+        assert!(last.verify(&vec![75], mt.root()));
+
+        // Every pair of neighbours in the unbalanced tree is adjacent by its paths
+        for leaf in &input[..6] {
+            let data = vec![leaf[0] + 5];
+            assert!(mt.prove_absence(&data).unwrap().verify(&data, mt.root()));
+        }
+
+        // A middle leaf cannot pose as the last one
+        let mut early = last.clone();
+        early.left = mt.prove_data(5);
+        assert!(!early.verify(&vec![75], mt.root()));
+    }
+}
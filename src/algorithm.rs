@@ -0,0 +1,86 @@
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+
+/// Digest algorithms that trees can be identified by in encoded form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Stable one-byte identifier of the algorithm
+    pub fn id(self) -> u8 {
+        match self {
+            HashAlgorithm::Sha224 => 1,
+            HashAlgorithm::Sha256 => 2,
+            HashAlgorithm::Sha384 => 3,
+            HashAlgorithm::Sha512 => 4,
+        }
+    }
+
+    /// Algorithm with the given identifier, see [`HashAlgorithm::id`]
+    pub fn from_id(id: u8) -> Option<HashAlgorithm> {
+        match id {
+            1 => Some(HashAlgorithm::Sha224),
+            2 => Some(HashAlgorithm::Sha256),
+            3 => Some(HashAlgorithm::Sha384),
+            4 => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Length in bytes of the hashes produced by the algorithm
+    pub fn output_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha224 => 28,
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha384 => 48,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+}
+
+/// A digest type with a known [`HashAlgorithm`] identifier
+pub trait KnownDigest: Digest {
+    const ALGORITHM: HashAlgorithm;
+}
+
+impl KnownDigest for Sha224 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha224;
+}
+
+impl KnownDigest for Sha256 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha256;
+}
+
+impl KnownDigest for Sha384 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha384;
+}
+
+impl KnownDigest for Sha512 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha512;
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+
+    use super::{HashAlgorithm, KnownDigest};
+
+    #[test]
+    fn identifiers_round_trip() {
+        for id in 0..=u8::MAX {
+            if let Some(algorithm) = HashAlgorithm::from_id(id) {
+                assert_eq!(algorithm.id(), id);
+            }
+        }
+
+        // Output lengths agree with the digest types
+        assert_eq!(Sha224::ALGORITHM.output_len(), Sha224::output_size());
+        assert_eq!(Sha256::ALGORITHM.output_len(), Sha256::output_size());
+        assert_eq!(Sha384::ALGORITHM.output_len(), Sha384::output_size());
+        assert_eq!(Sha512::ALGORITHM.output_len(), Sha512::output_size());
+    }
+}
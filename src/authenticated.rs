@@ -0,0 +1,50 @@
+use sha2::Digest;
+
+use crate::{Data, Hash, MerkleTree, Proof};
+
+/// A structure committed to by a root hash, whose leaves can be proven against that root
+pub trait AuthenticatedStructure {
+    /// Proof that a leaf belongs to the structure
+    type Proof;
+
+    /// Root hash committing to the whole structure
+    fn root(&self) -> Hash;
+
+    /// Verifies that the given leaf data belongs to this structure by the given proof
+    fn verify_proof(&self, leaf: &Data, proof: &Self::Proof) -> bool;
+}
+
+impl<D: Digest> AuthenticatedStructure for MerkleTree<D> {
+    type Proof = Proof<D>;
+
+    fn root(&self) -> Hash {
+        MerkleTree::root(self).clone()
+    }
+
+    fn verify_proof(&self, leaf: &Data, proof: &Proof<D>) -> bool {
+        proof.verify(leaf, MerkleTree::root(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AuthenticatedStructure, Hash, Sha256Tree};
+
+    fn root_of<T: AuthenticatedStructure>(t: &T) -> Hash {
+        t.root()
+    }
+
+    #[test]
+    fn generic_merkle_tree() {
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        assert_eq!(&root_of(&mt), mt.root());
+
+        let proof = mt.prove(2).unwrap();
+        assert!(AuthenticatedStructure::verify_proof(&mt, &input[2], &proof));
+        assert!(!AuthenticatedStructure::verify_proof(
+            &mt, &input[3], &proof
+        ));
+    }
+}
@@ -0,0 +1,66 @@
+use sha2::digest::consts::U32;
+use sha2::digest::{
+    FixedOutput, FixedOutputReset, HashMarker, Output, OutputSizeUser, Reset, Update,
+};
+
+/// BLAKE3 with 32-byte output, usable as the digest of a [`MerkleTree`](crate::MerkleTree)
+///
+/// BLAKE3 is much faster than SHA-256, but trees hashed with it have entirely different
+/// roots from the same leaves under SHA-256, so both sides of a proof must agree on it.
+#[derive(Debug, Clone, Default)]
+pub struct Blake3(::blake3::Hasher);
+
+impl HashMarker for Blake3 {}
+
+impl OutputSizeUser for Blake3 {
+    type OutputSize = U32;
+}
+
+impl Update for Blake3 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+}
+
+impl FixedOutput for Blake3 {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        out.copy_from_slice(self.0.finalize().as_bytes());
+    }
+}
+
+impl Reset for Blake3 {
+    fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+impl FixedOutputReset for Blake3 {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        out.copy_from_slice(self.0.finalize().as_bytes());
+        self.0.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Digest;
+
+    use crate::{Blake3, Blake3Tree, Sha256Tree};
+
+    #[test]
+    fn blake3_tree() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Blake3Tree::construct(&input);
+        assert_eq!(mt, Blake3Tree::construct(&input));
+        assert_ne!(mt.root(), Sha256Tree::construct(&input).root());
+
+        // Leaves are the prefixed BLAKE3 hash of their data
+        let leaf = Blake3Tree::construct(&input[..1]);
+        assert_eq!(leaf.root()[..], *::blake3::hash(&[0, 0]).as_bytes());
+        assert_eq!(leaf.root()[..], Blake3::digest([0, 0])[..]);
+
+        let proof = mt.prove(5).unwrap();
+        assert!(proof.verify(&input[5], mt.root()));
+    }
+}
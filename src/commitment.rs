@@ -0,0 +1,174 @@
+use alloc::vec::Vec;
+
+use crate::{Data, Hash, HashAlgorithm, KnownDigest, MerkleTree, Proof, ProofDecodeError};
+
+/// Version of the commitment layout written by [`Commitment::to_bytes`]
+const COMMITMENT_VERSION: u8 = 1;
+
+/// A published commitment to a tree: its root hash along with the metadata needed to
+/// interpret it
+///
+/// In binary form, a commitment is laid out as
+/// - one version byte, currently `1`
+/// - one byte identifying the digest algorithm, see [`HashAlgorithm::id`]
+/// - the leaf count as 8 bytes big endian
+/// - the root hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commitment {
+    algorithm: HashAlgorithm,
+    leaf_count: u64,
+    root: Hash,
+}
+
+impl<D: KnownDigest> MerkleTree<D> {
+    /// Commitment to the root hash and size of this tree
+    pub fn commitment(&self) -> Commitment {
+        Commitment {
+            algorithm: D::ALGORITHM,
+            leaf_count: self.leaf_count() as u64,
+            root: self.hash.clone(),
+        }
+    }
+}
+
+impl Commitment {
+    /// Layout version of the commitment
+    pub fn version(&self) -> u8 {
+        COMMITMENT_VERSION
+    }
+
+    /// Digest algorithm the tree is hashed with
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// Number of leaves in the tree
+    pub fn leaf_count(&self) -> u64 {
+        self.leaf_count
+    }
+
+    /// Root hash of the tree
+    pub fn root(&self) -> &Hash {
+        &self.root
+    }
+
+    /// Verifies that the given leaf data belongs to the committed tree by the given proof,
+    /// which must be hashed with the committed algorithm
+    pub fn verify_proof<D: KnownDigest>(&self, leaf: &Data, proof: &Proof<D>) -> bool {
+        D::ALGORITHM == self.algorithm && proof.verify(leaf, &self.root)
+    }
+
+    /// Encodes the commitment in its fixed binary layout
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(10 + self.root.len());
+        bytes.push(COMMITMENT_VERSION);
+        bytes.push(self.algorithm.id());
+        bytes.extend_from_slice(&self.leaf_count.to_be_bytes());
+        bytes.extend_from_slice(&self.root);
+        bytes
+    }
+
+    /// Decodes a commitment from its binary layout, see [`Commitment::to_bytes`]
+    /// Unknown versions and algorithms as well as empty trees are rejected
+    pub fn from_bytes(bytes: &[u8]) -> Result<Commitment, ProofDecodeError> {
+        let [version, algorithm, rest @ ..] = bytes else {
+            return Err(ProofDecodeError::Truncated);
+        };
+        if *version != COMMITMENT_VERSION {
+            return Err(ProofDecodeError::InvalidEncoding);
+        }
+        let algorithm =
+            HashAlgorithm::from_id(*algorithm).ok_or(ProofDecodeError::InvalidEncoding)?;
+
+        let (leaf_count, root) = rest
+            .split_at_checked(8)
+            .ok_or(ProofDecodeError::Truncated)?;
+        let leaf_count = u64::from_be_bytes(leaf_count.try_into().unwrap());
+        if leaf_count == 0 {
+            return Err(ProofDecodeError::InvalidEncoding);
+        }
+
+        match root.len().cmp(&algorithm.output_len()) {
+            core::cmp::Ordering::Less => Err(ProofDecodeError::Truncated),
+            core::cmp::Ordering::Greater => Err(ProofDecodeError::TrailingBytes),
+            core::cmp::Ordering::Equal => Ok(Commitment {
+                algorithm,
+                leaf_count,
+                root: root.to_vec(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha512;
+
+    use crate::{Commitment, HashAlgorithm, MerkleTree, ProofDecodeError, Sha256Tree};
+
+    #[test]
+    fn round_trip() {
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let commitment = mt.commitment();
+        assert_eq!(commitment.leaf_count(), 5);
+        assert_eq!(commitment.algorithm(), HashAlgorithm::Sha256);
+        assert_eq!(commitment.root(), mt.root());
+
+        let bytes = commitment.to_bytes();
+        assert_eq!(bytes.len(), 1 + 1 + 8 + 32);
+        assert_eq!(Commitment::from_bytes(&bytes), Ok(commitment.clone()));
+
+        assert!(commitment.verify_proof(&input[2], &mt.prove(2).unwrap()));
+        assert!(!commitment.verify_proof(&input[3], &mt.prove(2).unwrap()));
+
+        // A proof over another digest never verifies, even for the same data
+        let other = MerkleTree::<Sha512>::construct(&input);
+        assert!(!commitment.verify_proof(&input[2], &other.prove(2).unwrap()));
+    }
+
+    #[test]
+    fn malformed_bytes() {
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let bytes = MerkleTree::<Sha512>::construct(&input)
+            .commitment()
+            .to_bytes();
+        assert_eq!(bytes.len(), 1 + 1 + 8 + 64);
+        assert!(Commitment::from_bytes(&bytes).is_ok());
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 2;
+        assert_eq!(
+            Commitment::from_bytes(&wrong_version),
+            Err(ProofDecodeError::InvalidEncoding)
+        );
+
+        let mut unknown_algorithm = bytes.clone();
+        unknown_algorithm[1] = 0;
+        assert_eq!(
+            Commitment::from_bytes(&unknown_algorithm),
+            Err(ProofDecodeError::InvalidEncoding)
+        );
+
+        assert_eq!(
+            Commitment::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ProofDecodeError::Truncated)
+        );
+        assert_eq!(
+            Commitment::from_bytes(&bytes[..5]),
+            Err(ProofDecodeError::Truncated)
+        );
+        assert_eq!(
+            Commitment::from_bytes(&[]),
+            Err(ProofDecodeError::Truncated)
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Commitment::from_bytes(&trailing),
+            Err(ProofDecodeError::TrailingBytes)
+        );
+    }
+}
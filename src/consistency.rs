@@ -0,0 +1,230 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
+
+use crate::{ct_eq, hash_concat, Hash, MerkleTree, MerkleTreeChildren};
+
+/// A proof that a tree of some older size is a prefix of a newer tree, as in RFC 6962
+///
+/// Only leaves are ever appended between the two versions, so the older root can be
+/// recomputed from a handful of node hashes that the newer root is also built from.
+pub struct ConsistencyProof<D = Sha256> {
+    /// Leaf count of the newer tree
+    pub(crate) new_size: usize,
+    /// Node hashes in RFC 6962 order, from the bottom of the tree upwards
+    pub(crate) hashes: Vec<Hash>,
+    pub(crate) digest: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Generates a proof that the first `old_size` leaves of `new_tree` form the older tree
+    /// Returns `None` unless `0 < old_size <= new_tree.leaf_count()`
+    pub fn consistency_proof(
+        old_size: usize,
+        new_tree: &MerkleTree<D>,
+    ) -> Option<ConsistencyProof<D>> {
+        let new_size = new_tree.leaf_count();
+        if old_size == 0 || old_size > new_size {
+            return None;
+        }
+
+        let mut hashes = Vec::new();
+        new_tree.consistency_subproof(old_size, true, &mut hashes);
+
+        Some(ConsistencyProof {
+            new_size,
+            hashes,
+            digest: PhantomData,
+        })
+    }
+
+    /// Collects the RFC 6962 `SUBPROOF(m, D[n], b)` of this subtree
+    /// `complete` is true while the subtree's first `m` leaves form the whole older tree
+    fn consistency_subproof(&self, m: usize, complete: bool, hashes: &mut Vec<Hash>) {
+        let n = self.leaf_count();
+        if m == n {
+            // The older root is known to the verifier unless it is a proper subtree
+            if !complete {
+                hashes.push(self.hash.clone());
+            }
+            return;
+        }
+
+        let MerkleTreeChildren::Branch { left, right } = &self.children else {
+            unreachable!("a subtree with more than `m` leaves must be a branch");
+        };
+
+        let k = left.leaf_count();
+        if m <= k {
+            left.consistency_subproof(m, complete, hashes);
+            hashes.push(right.hash.clone());
+        } else {
+            right.consistency_subproof(m - k, false, hashes);
+            hashes.push(left.hash.clone());
+        }
+    }
+}
+
+impl<D: Digest> ConsistencyProof<D> {
+    /// Verifies that the tree with `old_root` over `old_size` leaves is a prefix of the
+    /// tree with `new_root` over `new_size` leaves
+    /// Both sizes must be trusted values, such as the ones signed tree heads commit to, since
+    /// the proof only holds for the sizes it was generated for.
+    pub fn verify(
+        &self,
+        old_root: &Hash,
+        new_root: &Hash,
+        old_size: usize,
+        new_size: usize,
+    ) -> bool {
+        if new_size != self.new_size || old_size == 0 || old_size > new_size {
+            return false;
+        }
+        if old_size == new_size {
+            return self.hashes.is_empty() && ct_eq(old_root, new_root);
+        }
+
+        // The older root is implicitly the first hash when it is a complete subtree
+        let mut path = self.hashes.iter();
+        let first = if old_size.is_power_of_two() {
+            old_root
+        } else {
+            match path.next() {
+                Some(hash) => hash,
+                None => return false,
+            }
+        };
+
+        // Node indices of the last leaf of each tree within their level
+        let mut old_index = old_size - 1;
+        let mut new_index = new_size - 1;
+        while old_index & 1 == 1 {
+            old_index >>= 1;
+            new_index >>= 1;
+        }
+
+        let mut old_hash = first.clone();
+        let mut new_hash = first.clone();
+        for sibling in path {
+            if new_index == 0 {
+                return false;
+            }
+
+            if old_index & 1 == 1 || old_index == new_index {
+                old_hash = hash_concat::<D>(sibling, &old_hash);
+                new_hash = hash_concat::<D>(sibling, &new_hash);
+                // Skip the levels where the lone right-most node is carried up unchanged
+                while old_index & 1 == 0 && old_index != 0 {
+                    old_index >>= 1;
+                    new_index >>= 1;
+                }
+            } else {
+                new_hash = hash_concat::<D>(&new_hash, sibling);
+            }
+
+            old_index >>= 1;
+            new_index >>= 1;
+        }
+
+        new_index == 0 && ct_eq(&old_hash, old_root) && ct_eq(&new_hash, new_root)
+    }
+}
+
+impl<D> fmt::Debug for ConsistencyProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConsistencyProof")
+            .field("new_size", &self.new_size)
+            .field("hashes", &self.hashes)
+            .finish()
+    }
+}
+
+impl<D> Clone for ConsistencyProof<D> {
+    fn clone(&self) -> Self {
+        ConsistencyProof {
+            new_size: self.new_size,
+            hashes: self.hashes.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialEq for ConsistencyProof<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.new_size == other.new_size && self.hashes == other.hashes
+    }
+}
+
+impl<D> Eq for ConsistencyProof<D> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::Sha256Tree;
+
+    #[test]
+    fn consistent_prefix() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let old = Sha256Tree::construct(&input[..4]);
+        let new = Sha256Tree::construct(&input);
+
+        let proof = Sha256Tree::consistency_proof(4, &new).unwrap();
+        assert!(proof.verify(&old.hash, &new.hash, 4, 8));
+    }
+
+    #[test]
+    fn trusted_sizes() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let old = Sha256Tree::construct(&input[..4]);
+        let new = Sha256Tree::construct(&input);
+
+        let proof = Sha256Tree::consistency_proof(4, &new).unwrap();
+        assert!(!proof.verify(&old.hash, &new.hash, 4, 7));
+        assert!(!proof.verify(&old.hash, &new.hash, 4, 9));
+
+        // A proof claiming another size fails against the trusted one, even if it is empty
+        let mut forged = Sha256Tree::consistency_proof(8, &new).unwrap();
+        assert!(forged.verify(&new.hash, &new.hash, 8, 8));
+        forged.new_size = 4;
+        assert!(!forged.verify(&old.hash, &old.hash, 4, 8));
+        assert!(!forged.verify(&new.hash, &new.hash, 8, 8));
+    }
+
+    #[test]
+    fn all_prefix_sizes() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        for new_size in 1..=input.len() {
+            let new = Sha256Tree::construct(&input[..new_size]);
+            for old_size in 1..=new_size {
+                let old = Sha256Tree::construct(&input[..old_size]);
+
+                let proof = Sha256Tree::consistency_proof(old_size, &new).unwrap();
+                assert!(proof.verify(&old.hash, &new.hash, old_size, new_size));
+                if old_size < new_size {
+                    assert!(!proof.verify(&new.hash, &old.hash, old_size, new_size));
+                }
+            }
+        }
+
+        let new = Sha256Tree::construct(&input);
+        assert!(Sha256Tree::consistency_proof(0, &new).is_none());
+        assert!(Sha256Tree::consistency_proof(9, &new).is_none());
+    }
+
+    #[test]
+    fn diverging_prefix() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mut other = input.clone();
+        other[2][0] += 1; // Mutate to non-original value
+
+        let old = Sha256Tree::construct(&other[..4]);
+        let new = Sha256Tree::construct(&input);
+
+        let proof = Sha256Tree::consistency_proof(4, &new).unwrap();
+        assert!(!proof.verify(&old.hash, &new.hash, 4, 8));
+    }
+}
@@ -0,0 +1,253 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+
+use crate::proof::Directions;
+use crate::{
+    check_leaf_count, hash_concat, hash_data, Data, Hash, HashAlgorithm, KnownDigest, MerkleError,
+    Proof,
+};
+
+/// Leaf and branch hashing of a digest chosen at runtime
+pub trait DynHasher: Send + Sync {
+    /// Hash of a leaf holding the given data
+    fn hash_leaf(&self, data: &[u8]) -> Hash;
+
+    /// Hash of a branch with the given child hashes
+    fn hash_branch(&self, left: &Hash, right: &Hash) -> Hash;
+}
+
+struct DigestHasher<D>(PhantomData<fn() -> D>);
+
+impl<D: Digest> DynHasher for DigestHasher<D> {
+    fn hash_leaf(&self, data: &[u8]) -> Hash {
+        hash_data::<D>(data)
+    }
+
+    fn hash_branch(&self, left: &Hash, right: &Hash) -> Hash {
+        hash_concat::<D>(left, right)
+    }
+}
+
+/// A Merkle tree whose digest algorithm is picked at runtime
+///
+/// Every level is kept as a list of hashes, from the leaves up to the root, and a lone
+/// right-most node is carried up as in [`MerkleTree`](crate::MerkleTree). The tree has the
+/// same hashes as a `MerkleTree` of the matching digest over the same input.
+pub struct DynTree {
+    algorithm: HashAlgorithm,
+    hasher: Box<dyn DynHasher>,
+    levels: Vec<Vec<Hash>>,
+}
+
+impl DynTree {
+    /// Constructs a tree hashed with the given algorithm from given leaf blobs
+    /// Length of the input must be nonzero
+    pub fn construct(algorithm: HashAlgorithm, input: &[Data]) -> DynTree {
+        Self::try_construct(algorithm, input).unwrap()
+    }
+
+    /// Constructs a tree hashed with the given algorithm from given leaf blobs, failing on
+    /// empty or oversized input
+    pub fn try_construct(algorithm: HashAlgorithm, input: &[Data]) -> Result<DynTree, MerkleError> {
+        check_leaf_count(input.len())?;
+
+        let hasher: Box<dyn DynHasher> = match algorithm {
+            HashAlgorithm::Sha224 => Box::new(DigestHasher::<Sha224>(PhantomData)),
+            HashAlgorithm::Sha256 => Box::new(DigestHasher::<Sha256>(PhantomData)),
+            HashAlgorithm::Sha384 => Box::new(DigestHasher::<Sha384>(PhantomData)),
+            HashAlgorithm::Sha512 => Box::new(DigestHasher::<Sha512>(PhantomData)),
+        };
+
+        let leaves: Vec<_> = input.iter().map(|item| hasher.hash_leaf(item)).collect();
+        let mut levels = alloc::vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hasher.hash_branch(left, right),
+                    [lone] => lone.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Ok(DynTree {
+            algorithm,
+            hasher,
+            levels,
+        })
+    }
+
+    /// Digest algorithm the tree is hashed with
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// Hashing of the tree's digest algorithm, e.g. to hash leaves for lookups
+    pub fn hasher(&self) -> &dyn DynHasher {
+        self.hasher.as_ref()
+    }
+
+    /// Root hash of the tree
+    pub fn root(&self) -> &Hash {
+        &self.levels.last().unwrap()[0]
+    }
+
+    /// Number of leaves in the tree
+    pub fn leaf_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Generates an inclusion proof for the leaf at the given index
+    /// Returns `None` if the index is out of range or `D` is not the digest of the tree
+    pub fn prove<D: KnownDigest>(&self, index: usize) -> Option<Proof<D>> {
+        if D::ALGORITHM != self.algorithm || index >= self.leaf_count() {
+            return None;
+        }
+
+        let mut siblings = Vec::new();
+        let mut directions = Directions::default();
+
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            // A carried node has no sibling on its level
+            if let Some(sibling) = level.get(index ^ 1) {
+                siblings.push(sibling.clone());
+                directions.push(index % 2 == 1);
+            }
+            index /= 2;
+        }
+
+        Some(Proof {
+            siblings,
+            directions,
+            digest: PhantomData,
+        })
+    }
+
+    /// Generates an inclusion proof for the leaf at the given index under the digest of the
+    /// tree, encoded as by [`Proof::to_bytes`]
+    /// Returns `None` if the index is out of range
+    pub fn prove_bytes(&self, index: usize) -> Option<Vec<u8>> {
+        match self.algorithm {
+            HashAlgorithm::Sha224 => self.prove::<Sha224>(index).map(|proof| proof.to_bytes()),
+            HashAlgorithm::Sha256 => self.prove::<Sha256>(index).map(|proof| proof.to_bytes()),
+            HashAlgorithm::Sha384 => self.prove::<Sha384>(index).map(|proof| proof.to_bytes()),
+            HashAlgorithm::Sha512 => self.prove::<Sha512>(index).map(|proof| proof.to_bytes()),
+        }
+    }
+
+    /// Verifies that the given leaf data belongs to the tree by an encoded proof, such as
+    /// one from [`DynTree::prove_bytes`], decoded and checked under the digest of the tree
+    /// Proofs that do not decode never verify
+    pub fn verify_proof(&self, leaf: &Data, proof: &[u8]) -> bool {
+        match self.algorithm {
+            HashAlgorithm::Sha224 => verify_encoded::<Sha224>(leaf, proof, self.root()),
+            HashAlgorithm::Sha256 => verify_encoded::<Sha256>(leaf, proof, self.root()),
+            HashAlgorithm::Sha384 => verify_encoded::<Sha384>(leaf, proof, self.root()),
+            HashAlgorithm::Sha512 => verify_encoded::<Sha512>(leaf, proof, self.root()),
+        }
+    }
+}
+
+/// Whether the encoded proof decodes under `D` and proves the leaf against the root hash
+fn verify_encoded<D: Digest>(leaf: &Data, proof: &[u8], root_hash: &Hash) -> bool {
+    Proof::<D>::from_bytes(proof).is_ok_and(|proof| proof.verify(leaf, root_hash))
+}
+
+impl fmt::Debug for DynTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DynTree")
+            .field("algorithm", &self.algorithm)
+            .field("levels", &self.levels)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Sha256, Sha512};
+
+    use crate::{DynTree, HashAlgorithm, MerkleTree};
+
+    #[test]
+    fn runtime_verification() {
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+
+        let algorithms = [
+            HashAlgorithm::Sha224,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+        ];
+        let trees: Vec<_> = algorithms
+            .iter()
+            .map(|&algorithm| DynTree::construct(algorithm, &input))
+            .collect();
+
+        for tree in &trees {
+            for (i, leaf) in input.iter().enumerate() {
+                let proof = tree.prove_bytes(i).unwrap();
+                assert!(tree.verify_proof(leaf, &proof));
+                assert!(!tree.verify_proof(&vec![42], &proof));
+                assert!(!tree.verify_proof(leaf, &proof[..proof.len() - 1]));
+            }
+            assert!(tree.prove_bytes(7).is_none());
+        }
+
+        // Proofs of one algorithm do not verify under another
+        let proof = trees[1].prove_bytes(3).unwrap();
+        let mut others = trees
+            .iter()
+            .filter(|tree| tree.algorithm() != trees[1].algorithm());
+        assert!(others.all(|tree| !tree.verify_proof(&input[3], &proof)));
+    }
+
+    #[test]
+    fn runtime_algorithms() {
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+
+        let algorithms = [HashAlgorithm::Sha256, HashAlgorithm::Sha512];
+        let trees: Vec<_> = algorithms
+            .iter()
+            .map(|&algorithm| DynTree::construct(algorithm, &input))
+            .collect();
+        assert_ne!(trees[0].root(), trees[1].root());
+        assert_eq!(trees[1].root().len(), 64);
+
+        assert_eq!(
+            trees[0].root(),
+            MerkleTree::<Sha256>::construct(&input).root()
+        );
+        assert_eq!(
+            trees[1].root(),
+            MerkleTree::<Sha512>::construct(&input).root()
+        );
+
+        for (i, leaf) in input.iter().enumerate() {
+            let proof = trees[0].prove::<Sha256>(i).unwrap();
+            assert!(proof.verify(leaf, trees[0].root()));
+            assert_eq!(
+                proof,
+                MerkleTree::<Sha256>::construct(&input).prove(i).unwrap()
+            );
+
+            let proof = trees[1].prove::<Sha512>(i).unwrap();
+            assert!(proof.verify(leaf, trees[1].root()));
+        }
+
+        // Proofs only come out under the digest of the tree
+        assert!(trees[0].prove::<Sha512>(0).is_none());
+        assert!(trees[0].prove::<Sha256>(7).is_none());
+        assert_eq!(
+            &trees[0].hasher().hash_leaf(&input[6]),
+            MerkleTree::<Sha256>::construct(&input[6..]).root()
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
+
+use crate::{empty_hashes, Hash};
+
+/// A table of the hashes of subtrees whose leaves are all empty, one per height
+///
+/// Height 0 is the [`MerkleTree::empty_leaf_hash`] sentinel and every other entry hashes two
+/// copies of the one below, computed once when the table is built. [`SparseMerkleTree`] keeps
+/// one for its default hashes, and padded trees take their empty subtrees from one.
+///
+/// [`MerkleTree::empty_leaf_hash`]: crate::MerkleTree::empty_leaf_hash
+/// [`SparseMerkleTree`]: crate::SparseMerkleTree
+pub struct EmptyHashes<D = Sha256> {
+    /// Hashes by height, from a single empty leaf up to the maximum height
+    hashes: Vec<Hash>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> EmptyHashes<D> {
+    /// Builds the table for all heights up to `max_height`, inclusive
+    pub fn new(max_height: usize) -> EmptyHashes<D> {
+        EmptyHashes {
+            hashes: empty_hashes::<D>(max_height),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> EmptyHashes<D> {
+    /// Greatest height in the table
+    pub fn max_height(&self) -> usize {
+        self.hashes.len() - 1
+    }
+
+    /// Hash of an empty subtree of the given height, `None` above the maximum height
+    pub fn subtree_hash(&self, height: usize) -> Option<&Hash> {
+        self.hashes.get(height)
+    }
+}
+
+impl<D> fmt::Debug for EmptyHashes<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EmptyHashes")
+            .field("hashes", &self.hashes)
+            .finish()
+    }
+}
+
+impl<D> Clone for EmptyHashes<D> {
+    fn clone(&self) -> Self {
+        EmptyHashes {
+            hashes: self.hashes.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialEq for EmptyHashes<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hashes == other.hashes
+    }
+}
+
+impl<D> Eq for EmptyHashes<D> {}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::EmptyHashes;
+    use crate::{hash_concat, SparseMerkleTree};
+
+    #[test]
+    fn empty_subtree_hashes() {
+        let table = EmptyHashes::<Sha256>::new(256);
+        assert_eq!(table.max_height(), 256);
+
+        let empty = table.subtree_hash(0).unwrap();
+        assert_eq!(empty, &vec![0; 32]);
+        assert_eq!(
+            table.subtree_hash(1).unwrap(),
+            &hash_concat::<Sha256>(empty, empty)
+        );
+        assert_eq!(table.subtree_hash(257), None);
+
+        // The root of an empty sparse tree is the empty subtree as high as the digest is long
+        let smt = SparseMerkleTree::<Sha256>::new();
+        assert_eq!(table.subtree_hash(256), Some(&smt.root()));
+        assert_eq!(smt.empty_hashes(), &table);
+
+        // A shorter table agrees with the longer one where both are defined
+        let short = EmptyHashes::<Sha256>::new(3);
+        assert_eq!(short.subtree_hash(3), table.subtree_hash(3));
+    }
+}
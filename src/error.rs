@@ -0,0 +1,176 @@
+use core::fmt;
+
+use crate::encoding::to_hex;
+use crate::{Hash, MAX_LEAVES};
+
+/// Errors returned by fallible Merkle tree operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MerkleError {
+    /// A tree needs at least one leaf
+    EmptyInput,
+    /// A leaf index is not smaller than the leaf count of the tree
+    IndexOutOfRange { index: usize, len: usize },
+    /// The input has more than [`MAX_LEAVES`](crate::MAX_LEAVES) leaves
+    TooLarge { len: usize },
+    /// Two subtrees to be joined have different depths
+    DepthMismatch { left: usize, right: usize },
+    /// A leaf count that must be a power of two is not one
+    NotPowerOfTwo(usize),
+    /// An input does not have the number of leaves it was expected to have
+    LengthMismatch { expected: usize, actual: usize },
+    /// A tree does not have the root hash it was expected to have
+    RootMismatch { expected: Hash, actual: Hash },
+    /// The hashing scheme of a tree does not allow the operation, e.g. hashing new leaf data
+    /// under a key the tree does not keep, or joining trees of different schemes
+    UnsupportedScheme,
+    /// A branch must have at least two children
+    InvalidArity(usize),
+}
+
+/// Errors returned when generating a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofError {
+    /// The leaf index is not smaller than the leaf count of the tree
+    IndexOutOfRange { index: usize, len: usize },
+}
+
+/// Errors returned when parsing an encoded proof or commitment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofDecodeError {
+    /// The input is not a well-formed encoding
+    InvalidEncoding,
+    /// The input ends before the value it describes
+    Truncated,
+    /// The input continues past the end of the value
+    TrailingBytes,
+}
+
+impl fmt::Display for MerkleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MerkleError::EmptyInput => write!(f, "cannot build a tree without leaves"),
+            MerkleError::IndexOutOfRange { index, len } => {
+                write!(f, "leaf index {index} is out of range for {len} leaves")
+            }
+            MerkleError::TooLarge { len } => {
+                write!(f, "{len} leaves exceed the maximum of {MAX_LEAVES}")
+            }
+            MerkleError::DepthMismatch { left, right } => {
+                write!(f, "cannot join subtrees of depths {left} and {right}")
+            }
+            MerkleError::NotPowerOfTwo(count) => write!(f, "{count} is not a power of two"),
+            MerkleError::LengthMismatch { expected, actual } => {
+                write!(f, "expected {expected} leaves but got {actual}")
+            }
+            MerkleError::RootMismatch { expected, actual } => write!(
+                f,
+                "root hash {} does not match the expected {}",
+                to_hex(actual),
+                to_hex(expected)
+            ),
+            MerkleError::UnsupportedScheme => {
+                write!(f, "the hashing scheme of the tree does not support this")
+            }
+            MerkleError::InvalidArity(arity) => {
+                write!(f, "an arity of {arity} is below the minimum of 2")
+            }
+        }
+    }
+}
+
+impl From<ProofError> for MerkleError {
+    fn from(error: ProofError) -> Self {
+        match error {
+            ProofError::IndexOutOfRange { index, len } => {
+                MerkleError::IndexOutOfRange { index, len }
+            }
+        }
+    }
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        MerkleError::from(self.clone()).fmt(f)
+    }
+}
+
+impl fmt::Display for ProofDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ProofDecodeError::InvalidEncoding => "invalid encoding",
+            ProofDecodeError::Truncated => "input ends before the encoded value",
+            ProofDecodeError::TrailingBytes => "input continues past the encoded value",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MerkleError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProofError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProofDecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MerkleError, ProofDecodeError, ProofError};
+
+    #[test]
+    fn display() {
+        let messages = [
+            MerkleError::EmptyInput.to_string(),
+            MerkleError::IndexOutOfRange { index: 9, len: 8 }.to_string(),
+            MerkleError::TooLarge { len: 7 }.to_string(),
+            MerkleError::DepthMismatch { left: 3, right: 2 }.to_string(),
+            MerkleError::NotPowerOfTwo(6).to_string(),
+            MerkleError::LengthMismatch {
+                expected: 5,
+                actual: 4,
+            }
+            .to_string(),
+            MerkleError::RootMismatch {
+                expected: vec![0xab; 2],
+                actual: vec![0xcd; 2],
+            }
+            .to_string(),
+            MerkleError::UnsupportedScheme.to_string(),
+            MerkleError::InvalidArity(1).to_string(),
+        ];
+        assert!(messages[0].contains("without leaves"));
+        assert!(messages[1].contains('9') && messages[1].contains('8'));
+        assert!(messages[2].contains('7'));
+        assert!(messages[3].contains('3') && messages[3].contains('2'));
+        assert!(messages[4].contains('6'));
+        assert!(messages[5].contains('5') && messages[5].contains('4'));
+        assert!(messages[6].contains("abab") && messages[6].contains("cdcd"));
+        assert!(messages[7].contains("scheme"));
+        assert!(messages[8].contains('1'));
+
+        let error = ProofError::IndexOutOfRange { index: 9, len: 8 };
+        assert_eq!(error.to_string(), messages[1]);
+        assert!(!ProofDecodeError::Truncated.to_string().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn question_mark() {
+        use crate::Sha256Tree;
+
+        fn prove_last(len: usize) -> Result<usize, Box<dyn std::error::Error>> {
+            let input = vec![vec![0]; len];
+            let mt = Sha256Tree::try_construct(&input)?;
+            let proof = mt.try_prove(len)?;
+            Ok(proof.siblings().len())
+        }
+
+        let error = prove_last(0).unwrap_err();
+        assert_eq!(error.to_string(), MerkleError::EmptyInput.to_string());
+        let error = prove_last(4).unwrap_err();
+        assert!(error.to_string().contains("out of range"));
+
+        let error: MerkleError = ProofError::IndexOutOfRange { index: 4, len: 4 }.into();
+        assert_eq!(error, MerkleError::IndexOutOfRange { index: 4, len: 4 });
+    }
+}
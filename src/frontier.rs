@@ -0,0 +1,280 @@
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    ct_eq, hash_concat, hash_data, merge_hashes, Builder, Data, Hash, MerkleTree, ProofDecodeError,
+    MAX_LEAVES,
+};
+
+/// The compact state of an append-only tree, enough to keep computing its root
+///
+/// Only the roots of the perfect subtrees along the right edge of the tree are kept, one per
+/// set bit of the leaf count, so the state stays `O(log n)` however many leaves are appended.
+/// The root is always the same as that of a [`MerkleTree`] over all appended leaves.
+pub struct MerkleFrontier<D = Sha256> {
+    builder: Builder<Hash>,
+    leaf_count: usize,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> MerkleFrontier<D> {
+    /// Creates the frontier of an empty tree
+    pub fn new() -> MerkleFrontier<D> {
+        MerkleFrontier {
+            builder: Builder::with_depth(0, merge_hashes::<D>),
+            leaf_count: 0,
+            digest: PhantomData,
+        }
+    }
+
+    /// Appends a single leaf
+    pub fn append(&mut self, data: Data) {
+        self.append_ref(&data);
+    }
+
+    /// Appends a single leaf without taking ownership of its data
+    pub(crate) fn append_ref(&mut self, data: &Data) {
+        self.builder.push(hash_data::<D>(data));
+        self.leaf_count += 1;
+    }
+
+    /// Number of leaves appended so far
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Root hash of the tree over all leaves appended so far, or
+    /// [`MerkleTree::empty_root`] if there are none
+    pub fn root(&self) -> Hash {
+        // Fold the pending subtrees from the bottom up, like the builder does when finishing
+        let mut pending = self.builder.left_side.iter().flatten();
+        match pending.next() {
+            Some(lowest) => {
+                pending.fold(lowest.clone(), |right, left| hash_concat::<D>(left, &right))
+            }
+            None => MerkleTree::<D>::empty_root(),
+        }
+    }
+
+    /// Encodes the state needed to resume appending: the leaf count as a 64-bit big endian
+    /// integer, followed by the pending subtree roots from the lowest level up
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let pending = self.builder.left_side.iter().flatten();
+        let hash_len = <D as Digest>::output_size();
+
+        let mut bytes = Vec::with_capacity(8 + self.leaf_count.count_ones() as usize * hash_len);
+        bytes.extend_from_slice(&(self.leaf_count as u64).to_be_bytes());
+        for hash in pending {
+            bytes.extend_from_slice(hash);
+        }
+        bytes
+    }
+
+    /// Restores a frontier from its encoding, see [`MerkleFrontier::to_bytes`]
+    /// Appending to the restored frontier gives the same roots as appending to the original
+    pub fn from_bytes(bytes: &[u8]) -> Result<MerkleFrontier<D>, ProofDecodeError> {
+        let (leaf_count, mut rest) = bytes
+            .split_at_checked(8)
+            .ok_or(ProofDecodeError::Truncated)?;
+        let leaf_count = u64::from_be_bytes(leaf_count.try_into().unwrap());
+        let leaf_count = usize::try_from(leaf_count)
+            .ok()
+            .filter(|&count| count <= MAX_LEAVES)
+            .ok_or(ProofDecodeError::InvalidEncoding)?;
+
+        // One pending subtree per set bit of the leaf count
+        let hash_len = <D as Digest>::output_size();
+        match rest
+            .len()
+            .cmp(&(leaf_count.count_ones() as usize * hash_len))
+        {
+            Ordering::Less => return Err(ProofDecodeError::Truncated),
+            Ordering::Greater => return Err(ProofDecodeError::TrailingBytes),
+            Ordering::Equal => {}
+        }
+
+        let levels = (usize::BITS - leaf_count.leading_zeros()) as usize;
+        let mut builder = Builder::with_depth(levels, merge_hashes::<D>);
+        for (level, slot) in builder.left_side.iter_mut().enumerate() {
+            if leaf_count >> level & 1 == 1 {
+                let (hash, tail) = rest.split_at(hash_len);
+                slot.push(hash.to_vec());
+                rest = tail;
+            }
+        }
+
+        Ok(MerkleFrontier {
+            builder,
+            leaf_count,
+            digest: PhantomData,
+        })
+    }
+}
+
+impl<D: Digest> Default for MerkleFrontier<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D> fmt::Debug for MerkleFrontier<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MerkleFrontier")
+            .field("left_side", &self.builder.left_side)
+            .field("leaf_count", &self.leaf_count)
+            .finish()
+    }
+}
+
+impl<D> Clone for MerkleFrontier<D> {
+    fn clone(&self) -> Self {
+        MerkleFrontier {
+            builder: Builder {
+                left_side: self.builder.left_side.clone(),
+                merge: self.builder.merge,
+            },
+            leaf_count: self.leaf_count,
+            digest: PhantomData,
+        }
+    }
+}
+
+/// Checks leaves streamed in one at a time against an expected root hash
+///
+/// The leaves are appended to a [`MerkleFrontier`], so only the `O(log n)` pending subtree
+/// roots are kept and datasets far larger than memory can be verified as they are read.
+pub struct StreamVerifier<D = Sha256> {
+    frontier: MerkleFrontier<D>,
+}
+
+impl<D: Digest> StreamVerifier<D> {
+    /// Creates a verifier that has seen no leaves yet
+    pub fn new() -> StreamVerifier<D> {
+        StreamVerifier {
+            frontier: MerkleFrontier::new(),
+        }
+    }
+
+    /// Feeds the next leaf
+    pub fn update(&mut self, leaf: Data) {
+        self.frontier.append(leaf);
+    }
+
+    /// Feeds the next leaf without taking ownership of its data
+    pub(crate) fn update_ref(&mut self, leaf: &Data) {
+        self.frontier.append_ref(leaf);
+    }
+
+    /// Whether the leaves fed so far produce the given root hash
+    /// No leaves never verify
+    pub fn finalize(self, expected_root: &Hash) -> bool {
+        self.frontier.leaf_count() > 0 && ct_eq(&self.frontier.root(), expected_root)
+    }
+}
+
+impl<D: Digest> Default for StreamVerifier<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D> fmt::Debug for StreamVerifier<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StreamVerifier")
+            .field("left_side", &self.frontier.builder.left_side)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::{MerkleFrontier, StreamVerifier};
+    use crate::{ProofDecodeError, Sha256Tree};
+
+    #[test]
+    fn matches_full_tree() {
+        let input: Vec<_> = (0..1000u32).map(|i| i.to_le_bytes().to_vec()).collect();
+
+        let mut frontier = MerkleFrontier::<Sha256>::new();
+        for (i, item) in input.iter().enumerate() {
+            frontier.append(item.clone());
+            if i % 97 == 0 {
+                assert_eq!(frontier.root(), Sha256Tree::construct(&input[..=i]).hash);
+            }
+        }
+
+        assert_eq!(frontier.leaf_count(), 1000);
+        assert_eq!(frontier.root(), Sha256Tree::construct(&input).hash);
+
+        // One pending subtree per set bit of the leaf count
+        assert_eq!(frontier.builder.left_side.iter().flatten().count(), 6);
+    }
+
+    #[test]
+    fn empty_frontier() {
+        let frontier = MerkleFrontier::<Sha256>::default();
+        assert_eq!(frontier.leaf_count(), 0);
+        assert_eq!(frontier.root(), Sha256Tree::empty_root());
+    }
+
+    #[test]
+    fn resume_from_bytes() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mut frontier = MerkleFrontier::<Sha256>::new();
+        for item in &input[..5] {
+            frontier.append(item.clone());
+        }
+        let bytes = frontier.to_bytes();
+        assert_eq!(bytes.len(), 8 + 2 * 32);
+
+        let mut restored = MerkleFrontier::<Sha256>::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.root(), frontier.root());
+        for item in &input[5..] {
+            restored.append(item.clone());
+        }
+        assert_eq!(restored.leaf_count(), 8);
+        assert_eq!(restored.root(), Sha256Tree::construct(&input).hash);
+
+        assert_eq!(
+            MerkleFrontier::<Sha256>::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            ProofDecodeError::Truncated
+        );
+        let mut long = bytes.clone();
+        long.push(0);
+        assert_eq!(
+            MerkleFrontier::<Sha256>::from_bytes(&long).unwrap_err(),
+            ProofDecodeError::TrailingBytes
+        );
+
+        let empty = MerkleFrontier::<Sha256>::from_bytes(&[0; 8]).unwrap();
+        assert_eq!(empty.root(), Sha256Tree::empty_root());
+    }
+
+    #[test]
+    fn stream_verifier() {
+        let input: Vec<_> = (0..256u32).map(|i| i.to_le_bytes().to_vec()).collect();
+        let root = Sha256Tree::construct(&input).hash;
+        let other = Sha256Tree::construct(&input[1..]).hash;
+
+        for expected in [&root, &other] {
+            let mut verifier = StreamVerifier::<Sha256>::new();
+            for item in &input {
+                verifier.update(item.clone());
+            }
+            assert!(verifier.frontier.builder.left_side.len() <= 9);
+            assert_eq!(
+                verifier.finalize(expected),
+                Sha256Tree::verify(&input, expected)
+            );
+        }
+
+        assert!(!StreamVerifier::<Sha256>::default().finalize(&root));
+    }
+}
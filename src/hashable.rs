@@ -0,0 +1,70 @@
+use sha2::Digest;
+
+use crate::{hash_data, Hash};
+
+/// A leaf value that knows how to hash itself into a leaf hash
+///
+/// Byte strings, anything that is `AsRef<[u8]>`, hash as plain leaves, `D(0x00 || data)`,
+/// so trees over them match trees over the same [`Data`](crate::Data). Other types should
+/// keep that `0x00` prefix in front of their encoding, so that no leaf can be passed off as
+/// an internal node.
+pub trait Hashable {
+    /// Leaf hash of the value under the digest `D`
+    fn hash<D: Digest>(&self) -> Hash;
+}
+
+impl<T: AsRef<[u8]> + ?Sized> Hashable for T {
+    fn hash<D: Digest>(&self) -> Hash {
+        hash_data::<D>(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use super::Hashable;
+    use crate::{Hash, Sha256Tree};
+
+    struct Point {
+        x: u32,
+        y: u32,
+    }
+
+    impl Hashable for Point {
+        fn hash<D: Digest>(&self) -> Hash {
+            D::new()
+                .chain_update([0x00])
+                .chain_update(self.x.to_le_bytes())
+                .chain_update(self.y.to_le_bytes())
+                .finalize()
+                .to_vec()
+        }
+    }
+
+    #[test]
+    fn custom_leaves() {
+        let points = [Point { x: 1, y: 2 }, Point { x: 3, y: 4 }];
+        let mt = Sha256Tree::construct(&points);
+
+        let leaf = |x: u32, y: u32| {
+            let bytes: Vec<u8> = [0x00]
+                .into_iter()
+                .chain(x.to_le_bytes())
+                .chain(y.to_le_bytes())
+                .collect();
+            Sha256::digest(bytes)
+        };
+        let (left, right) = (leaf(1, 2), leaf(3, 4));
+        let root = Sha256::new()
+            .chain_update([0x01])
+            .chain_update(left)
+            .chain_update(right)
+            .finalize();
+        assert_eq!(mt.root().as_slice(), root.as_slice());
+
+        // Byte strings still hash as plain leaves
+        let bytes = [[1u8, 0, 0, 0, 2, 0, 0, 0], [3, 0, 0, 0, 4, 0, 0, 0]];
+        assert_eq!(Sha256Tree::construct(&bytes).root(), mt.root());
+    }
+}
@@ -0,0 +1,302 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
+
+use crate::{check_leaf_count, ct_eq, hash_children, hash_data, Builder, Data, Hash, MerkleError};
+
+/// A Merkle (sub)tree whose branches have up to `arity` children
+///
+/// Leaves are grouped level by level, `arity` adjacent nodes at a time. A lone node left
+/// over at the end of a level is carried up unchanged, so an arity of 2 yields exactly the
+/// same roots as [`MerkleTree`](crate::MerkleTree), which is built by the same carry
+/// propagation with pairs of nodes.
+pub struct KaryMerkleTree<D = Sha256> {
+    hash: Hash,
+    /// Number of leaves below this node
+    leaf_count: usize,
+    /// Child subtrees from left to right, empty for leaves
+    children: Vec<KaryMerkleTree<D>>,
+    digest: PhantomData<fn() -> D>,
+}
+
+/// An inclusion proof for a single leaf of a k-ary Merkle tree
+pub struct KaryProof<D = Sha256> {
+    /// For each level from the leaf upwards, the position of the proven node among its
+    /// siblings and the hashes of all of those siblings, from left to right
+    pub(crate) levels: Vec<(usize, Vec<Hash>)>,
+    pub(crate) digest: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> KaryMerkleTree<D> {
+    /// Constructs a k-ary Merkle tree from given leaf blobs
+    /// Length of the input must be nonzero and the arity at least 2, see
+    /// [`KaryMerkleTree::try_construct`]
+    pub fn construct(input: &[Data], arity: usize) -> KaryMerkleTree<D> {
+        Self::try_construct(input, arity).unwrap()
+    }
+
+    /// Constructs a k-ary Merkle tree from given leaf blobs, failing on empty or oversized
+    /// input and on an arity below 2
+    pub fn try_construct(input: &[Data], arity: usize) -> Result<KaryMerkleTree<D>, MerkleError> {
+        check_leaf_count(input.len())?;
+        if arity < 2 {
+            return Err(MerkleError::InvalidArity(arity));
+        }
+
+        let mut builder = Builder::with_arity(arity, KaryMerkleTree::branch);
+        for item in input {
+            builder.push(KaryMerkleTree {
+                hash: hash_data::<D>(item),
+                leaf_count: 1,
+                children: Vec::new(),
+                digest: PhantomData,
+            });
+        }
+
+        Ok(builder.finish().unwrap())
+    }
+
+    fn branch(children: Vec<KaryMerkleTree<D>>) -> KaryMerkleTree<D> {
+        let hashes: Vec<_> = children.iter().map(|child| &child.hash).collect();
+        KaryMerkleTree {
+            hash: hash_children::<D>(&hashes),
+            leaf_count: children.iter().map(|child| child.leaf_count).sum(),
+            children,
+            digest: PhantomData,
+        }
+    }
+
+    /// Verifies that the given input data produces the given root hash
+    pub fn verify(input: &[Data], arity: usize, root_hash: &Hash) -> bool {
+        ct_eq(&Self::construct(input, arity).hash, root_hash)
+    }
+
+    /// Root hash of the tree
+    pub fn root(&self) -> &Hash {
+        &self.hash
+    }
+
+    /// Number of leaves in this (sub)tree
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Generates an inclusion proof for the leaf at the given index
+    /// Returns `None` if the index is out of range
+    pub fn prove(&self, index: usize) -> Option<KaryProof<D>> {
+        if index >= self.leaf_count {
+            return None;
+        }
+
+        let mut levels = Vec::new();
+        let mut node = self;
+        let mut index = index;
+        while !node.children.is_empty() {
+            let mut position = 0;
+            while index >= node.children[position].leaf_count {
+                index -= node.children[position].leaf_count;
+                position += 1;
+            }
+
+            let siblings = node
+                .children
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| i != position)
+                .map(|(_, child)| child.hash.clone())
+                .collect();
+            levels.push((position, siblings));
+            node = &node.children[position];
+        }
+        levels.reverse();
+
+        Some(KaryProof {
+            levels,
+            digest: PhantomData,
+        })
+    }
+}
+
+impl<D: Digest> KaryProof<D> {
+    /// Verifies that the given leaf data belongs to the tree of the given arity with the given
+    /// root hash
+    /// Proofs with a level of no siblings or more than `arity - 1` of them, or with siblings
+    /// that are not hashes of `D`, are rejected before hashing that level.
+    pub fn verify(&self, leaf: &Data, arity: usize, root_hash: &Hash) -> bool {
+        let hash_len = <D as Digest>::output_size();
+
+        let mut hash = hash_data::<D>(leaf);
+        for (position, siblings) in &self.levels {
+            if siblings.is_empty() || siblings.len() >= arity || *position > siblings.len() {
+                return false;
+            }
+            if siblings.iter().any(|sibling| sibling.len() != hash_len) {
+                return false;
+            }
+
+            let mut children: Vec<_> = siblings.iter().collect();
+            children.insert(*position, &hash);
+            hash = hash_children::<D>(&children);
+        }
+
+        ct_eq(&hash, root_hash)
+    }
+}
+
+impl<D> fmt::Debug for KaryMerkleTree<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KaryMerkleTree")
+            .field("hash", &self.hash)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+impl<D> Clone for KaryMerkleTree<D> {
+    fn clone(&self) -> Self {
+        KaryMerkleTree {
+            hash: self.hash.clone(),
+            leaf_count: self.leaf_count,
+            children: self.children.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialEq for KaryMerkleTree<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.children == other.children
+    }
+}
+
+impl<D> Eq for KaryMerkleTree<D> {}
+
+impl<D> fmt::Debug for KaryProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KaryProof")
+            .field("levels", &self.levels)
+            .finish()
+    }
+}
+
+impl<D> Clone for KaryProof<D> {
+    fn clone(&self) -> Self {
+        KaryProof {
+            levels: self.levels.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialEq for KaryProof<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.levels == other.levels
+    }
+}
+
+impl<D> Eq for KaryProof<D> {}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::KaryMerkleTree;
+    use crate::{hash_children, hash_data, MerkleError, Sha256Tree};
+
+    #[test]
+    fn quaternary_tree() {
+        let input: Vec<_> = (0..16).map(|i| vec![i]).collect();
+
+        let mt = KaryMerkleTree::<Sha256>::construct(&input, 4);
+        assert!(KaryMerkleTree::<Sha256>::verify(&input, 4, mt.root()));
+        assert_eq!(mt.children.len(), 4);
+
+        let proof = mt.prove(9).unwrap();
+        assert_eq!(proof.levels.len(), 2);
+        assert!(proof.verify(&input[9], 4, mt.root()));
+        assert!(!proof.verify(&input[8], 4, mt.root()));
+        assert!(mt.prove(16).is_none());
+    }
+
+    #[test]
+    fn malformed_proofs() {
+        let input: Vec<_> = (0..16).map(|i| vec![i]).collect();
+
+        let mt = KaryMerkleTree::<Sha256>::construct(&input, 4);
+        let proof = mt.prove(9).unwrap();
+
+        // Too many siblings for the arity, even if the root they hash to matches
+        let wide = KaryMerkleTree::<Sha256>::construct(&input, 5);
+        let extra = wide.prove(0).unwrap();
+        assert!(extra.verify(&input[0], 5, wide.root()));
+        assert!(!extra.verify(&input[0], 4, wide.root()));
+        assert!(!proof.verify(&input[9], 3, mt.root()));
+
+        let mut short = proof.clone();
+        short.levels[1].1[0].pop();
+        assert!(!short.verify(&input[9], 4, mt.root()));
+
+        let mut empty = proof;
+        empty.levels[0].1.clear();
+        empty.levels[0].0 = 0;
+        assert!(!empty.verify(&input[9], 4, mt.root()));
+    }
+
+    #[test]
+    fn partial_groups() {
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+
+        let mt = KaryMerkleTree::<Sha256>::construct(&input, 3);
+        for (i, leaf) in input.iter().enumerate() {
+            assert!(mt.prove(i).unwrap().verify(leaf, 3, mt.root()));
+        }
+    }
+
+    #[test]
+    fn binary_arity_matches_merkle_tree() {
+        for size in 1..=9 {
+            let input: Vec<_> = (0..size).map(|i| vec![i]).collect();
+
+            let mt = KaryMerkleTree::<Sha256>::construct(&input, 2);
+            assert_eq!(mt.root(), Sha256Tree::construct(&input).root());
+        }
+    }
+
+    #[test]
+    fn level_by_level_grouping() {
+        // 10 leaves in groups of 3: [0 1 2] [3 4 5] [6 7 8] [9], then [A B C] [9]
+        let input: Vec<_> = (0..10).map(|i| vec![i]).collect();
+        let leaf = |i: usize| hash_data::<Sha256>(&input[i]);
+        let group = |range: core::ops::Range<usize>| {
+            let hashes: Vec<_> = range.map(leaf).collect();
+            hash_children::<Sha256>(&hashes.iter().collect::<Vec<_>>())
+        };
+        let top = hash_children::<Sha256>(&[&group(0..3), &group(3..6), &group(6..9)]);
+        let root = hash_children::<Sha256>(&[&top, &leaf(9)]);
+
+        let mt = KaryMerkleTree::<Sha256>::construct(&input, 3);
+        assert_eq!(mt.root(), &root);
+        assert_eq!(mt.leaf_count(), 10);
+        assert_eq!(mt.children.len(), 2);
+    }
+
+    #[test]
+    fn try_construct() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+
+        let mt = KaryMerkleTree::<Sha256>::try_construct(&input, 4).unwrap();
+        assert_eq!(mt, KaryMerkleTree::construct(&input, 4));
+        assert_eq!(
+            KaryMerkleTree::<Sha256>::try_construct(&[], 4).unwrap_err(),
+            MerkleError::EmptyInput
+        );
+        for arity in [0, 1] {
+            assert_eq!(
+                KaryMerkleTree::<Sha256>::try_construct(&input, arity).unwrap_err(),
+                MerkleError::InvalidArity(arity)
+            );
+        }
+    }
+}
@@ -1,128 +1,3578 @@
-use sha2::Digest;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+
+use encoding::{to_base64, to_hex};
+
+use sha2::digest::core_api::BlockSizeUser;
+use sha2::{Digest, Sha256};
+
+mod absence;
+mod algorithm;
+mod authenticated;
+#[cfg(feature = "blake3")]
+mod blake3;
+mod commitment;
+mod consistency;
+mod dyn_tree;
+mod empty;
+mod encoding;
+mod error;
+mod frontier;
+mod hashable;
+#[cfg(feature = "serde")]
+mod hex_serde;
+mod kary;
+pub mod light;
+mod mmr;
+mod multiproof;
+mod partial;
+mod proof;
+#[cfg(test)]
+mod properties;
+mod range;
+mod shared;
+mod sparse;
+#[cfg(feature = "serde")]
+mod tree_serde;
+#[cfg(test)]
+mod vectors;
+
+#[cfg(feature = "blake3")]
+pub use crate::blake3::Blake3;
+pub use absence::AbsenceProof;
+pub use algorithm::{HashAlgorithm, KnownDigest};
+pub use authenticated::AuthenticatedStructure;
+pub use commitment::Commitment;
+pub use consistency::ConsistencyProof;
+pub use dyn_tree::{DynHasher, DynTree};
+pub use empty::EmptyHashes;
+pub use error::{MerkleError, ProofDecodeError, ProofError};
+pub use frontier::{MerkleFrontier, StreamVerifier};
+pub use hashable::Hashable;
+pub use kary::{KaryMerkleTree, KaryProof};
+pub use mmr::Mmr;
+pub use multiproof::MultiProof;
+pub use partial::PartialTree;
+pub use proof::{verify_proof_batch, BitOrder, Proof, ProvenLeaf, MAX_PROOF_LEVELS};
+pub use range::RangeProof;
+pub use shared::SharedMerkleTree;
+pub use sparse::{SparseMerkleTree, SparseProof};
 
 pub type Data = Vec<u8>;
 pub type Hash = Vec<u8>;
 
-/// A Merkle (sub)tree
-pub struct MerkleTree {
+/// Length in bytes of the hashes of a [`Sha256Tree`]
+/// Trees over other digests use [`MerkleTree::hash_len`]
+pub const HASH_LEN: usize = 32;
+
+/// Maximum number of leaves in a tree
+/// This keeps the total node count of a tree, `2 * leaves - 1`, within a `usize`, e.g.
+/// `2^31` leaves on 32-bit targets
+pub const MAX_LEAVES: usize = 1 << (usize::BITS - 1);
+
+/// Maximum depth of a tree, that of a tree with [`MAX_LEAVES`] leaves
+/// Deeper trees cannot be built, and traversals can rely on this bound
+pub const MAX_DEPTH: usize = usize::BITS as usize;
+
+/// A Merkle (sub)tree, hashed with the digest algorithm `D`
+///
+/// Leaves and internal nodes are domain-separated as in RFC 6962: a leaf hashes to
+/// `D(0x00 || data)` and a branch to `D(0x01 || left || right)`, so an internal node
+/// can never be passed off as a leaf.
+///
+/// Trees built with another leaf or branch scheme, such as [`MerkleTree::construct_prefixed`],
+/// remember it, so that mutations rehash the tree the way it was built.
+///
+/// With the `serde` feature, trees deeper than [`MAX_DEPTH`] are rejected on deserialization.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(bound = ""))]
+pub struct MerkleTree<D = Sha256> {
+    #[cfg_attr(feature = "serde", serde(with = "hex_serde"))]
     hash: Hash,
-    #[allow(dead_code)]
-    children: MerkleTreeChildren,
+    children: MerkleTreeChildren<D>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "HashScheme::is_standard")
+    )]
+    scheme: HashScheme,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    digest: PhantomData<fn() -> D>,
+}
+
+/// How the leaves and branches of a tree are hashed, kept in every node so that mutations
+/// and [`MerkleTree::validate`] hash the tree the way it was built
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum HashScheme {
+    /// Leaves as `D(0x00 || data)` and branches as `D(0x01 || left || right)`
+    #[default]
+    Standard,
+    /// Leaves salted with their index, see [`MerkleTree::construct_indexed`]
+    Indexed,
+    /// Branches with length-prefixed children, see [`MerkleTree::construct_prefixed`]
+    Prefixed,
+    /// Branches with a separator byte between the children, see
+    /// [`MerkleTree::construct_with_separator`]
+    Separated(u8),
+    /// Leaves hashed by a key or function the tree does not keep, so they cannot be rehashed
+    Opaque,
+}
+
+impl HashScheme {
+    #[cfg(feature = "serde")]
+    pub(crate) fn is_standard(&self) -> bool {
+        *self == HashScheme::Standard
+    }
+
+    /// Hash of a leaf holding the given data at the given index
+    fn hash_leaf<D: Digest>(self, index: usize, data: &Data) -> Result<Hash, MerkleError> {
+        match self {
+            HashScheme::Standard | HashScheme::Prefixed | HashScheme::Separated(_) => {
+                Ok(hash_data::<D>(data))
+            }
+            HashScheme::Indexed => Ok(hash_indexed::<D>(index, data)),
+            HashScheme::Opaque => Err(MerkleError::UnsupportedScheme),
+        }
+    }
+
+    /// Hash of a branch with the given child hashes
+    fn hash_branch<D: Digest>(self, left: &Hash, right: &Hash) -> Hash {
+        match self {
+            HashScheme::Prefixed => hash_concat_prefixed::<D>(left, right),
+            HashScheme::Separated(separator) => hash_concat_sep::<D>(left, right, Some(separator)),
+            HashScheme::Standard | HashScheme::Indexed | HashScheme::Opaque => {
+                hash_concat::<D>(left, right)
+            }
+        }
+    }
 }
 
+/// A Merkle tree hashed with SHA-256
+pub type Sha256Tree = MerkleTree<Sha256>;
+
+/// A Merkle tree hashed with BLAKE3
+#[cfg(feature = "blake3")]
+pub type Blake3Tree = MerkleTree<Blake3>;
+
 /// Potential children of a single Merkle tree node
-pub enum MerkleTreeChildren {
-    Leaf,
+///
+/// Leaves hold their data only in trees built by [`MerkleTree::construct_retaining`].
+/// Retained data is not serialized.
+pub enum MerkleTreeChildren<D = Sha256> {
+    Leaf {
+        data: Option<Data>,
+    },
     Branch {
-        left: Box<MerkleTree>,
-        right: Box<MerkleTree>,
+        left: Box<MerkleTree<D>>,
+        right: Box<MerkleTree<D>>,
     },
 }
 
-impl MerkleTree {
-    fn leaf(hash: Hash) -> MerkleTree {
+/// Progress reported by [`MerkleTree::construct_instrumented`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The leaf at the given index was hashed
+    LeafHashed(usize),
+    /// A branch was created at the given level, counted from the leaves at level 0
+    BranchCreated { level: usize },
+}
+
+impl<D: Digest> MerkleTree<D> {
+    fn leaf(hash: Hash) -> MerkleTree<D> {
+        Self::leaf_in(HashScheme::Standard, hash)
+    }
+
+    fn leaf_in(scheme: HashScheme, hash: Hash) -> MerkleTree<D> {
         MerkleTree {
             hash,
-            children: MerkleTreeChildren::Leaf,
+            children: MerkleTreeChildren::Leaf { data: None },
+            scheme,
+            digest: PhantomData,
+        }
+    }
+
+    /// Branch over two subtrees of the same scheme, hashed with that scheme
+    fn branch(left: MerkleTree<D>, right: MerkleTree<D>) -> MerkleTree<D> {
+        debug_assert_eq!(left.scheme, right.scheme);
+        let scheme = left.scheme;
+        MerkleTree {
+            hash: scheme.hash_branch::<D>(&left.hash, &right.hash),
+            children: MerkleTreeChildren::Branch {
+                left: Box::new(left),
+                right: Box::new(right),
+            },
+            scheme,
+            digest: PhantomData,
+        }
+    }
+
+    /// Joins two separately built trees of equal depth as the left and right halves of a new one
+    ///
+    /// The left tree must be perfect, i.e. hold a power of two of leaves, so that the result
+    /// has the same shape as [`MerkleTree::construct`] over the leaves of both in order. Both
+    /// trees must be hashed with the same scheme.
+    pub fn join(left: MerkleTree<D>, right: MerkleTree<D>) -> Result<MerkleTree<D>, MerkleError> {
+        if left.scheme != right.scheme {
+            return Err(MerkleError::UnsupportedScheme);
+        }
+
+        let depth = left.depth();
+        if depth != right.depth() {
+            return Err(MerkleError::DepthMismatch {
+                left: depth,
+                right: right.depth(),
+            });
+        }
+
+        let left_count = left.leaf_count();
+        if left_count != 1 << (depth - 1) {
+            return Err(MerkleError::NotPowerOfTwo(left_count));
+        }
+        check_leaf_count(left_count + right.leaf_count())?;
+
+        Ok(Self::branch(left, right))
+    }
+
+    /// Splits the tree into the left and right subtrees of its root, or gives it back as `Err`
+    /// if it is a single leaf
+    /// Halves of equal depth can be put back together with [`MerkleTree::join`].
+    pub fn split(self) -> Result<(MerkleTree<D>, MerkleTree<D>), MerkleTree<D>> {
+        match self.children {
+            MerkleTreeChildren::Branch { left, right } => Ok((*left, *right)),
+            MerkleTreeChildren::Leaf { .. } => Err(self),
+        }
+    }
+
+    /// Constructs a Merkle tree from given leaves, each hashed by its [`Hashable`] impl
+    /// Blobs such as [`Data`] hash as plain leaves. Length of the input must be nonzero, see
+    /// [`MerkleTree::try_construct`]
+    pub fn construct<T: Hashable>(input: &[T]) -> MerkleTree<D> {
+        Self::build(input, |item| Hashable::hash::<D>(item)).unwrap()
+    }
+
+    /// Constructs a Merkle tree from given leaf blobs, failing on empty or oversized input
+    ///
+    /// When a level has an odd number of nodes, the lone right-most node is carried up
+    /// unchanged (it is not duplicated) until it meets a left sibling. Equivalently, the
+    /// left subtree of every branch holds the largest power of two of leaves strictly
+    /// smaller than the branch's leaf count, so the shape depends only on the input length.
+    ///
+    /// Nodes are always paired from left to right in input order, leaf `2i` with leaf
+    /// `2i + 1` and likewise on every level above, with the left node hashed first. Equal
+    /// subtrees are no exception, so roots are reproducible across runs and platforms.
+    pub fn try_construct(input: &[Data]) -> Result<MerkleTree<D>, MerkleError> {
+        Self::build(input, |item| hash_data::<D>(item))
+    }
+
+    /// Constructs a Merkle tree from given leaf blobs, returning it only if its root hash is
+    /// the expected one
+    /// Length of the input must be nonzero
+    pub fn load_verified(
+        leaves: &[Data],
+        expected_root: &Hash,
+    ) -> Result<MerkleTree<D>, MerkleError> {
+        let tree = Self::try_construct(leaves)?;
+        if !ct_eq(&tree.hash, expected_root) {
+            return Err(MerkleError::RootMismatch {
+                expected: expected_root.clone(),
+                actual: tree.hash,
+            });
         }
+
+        Ok(tree)
+    }
+
+    /// Constructs a Merkle tree exactly as specified for Certificate Transparency logs by
+    /// RFC 6962, for interoperating with their verifiers
+    ///
+    /// This is the layout [`MerkleTree::try_construct`] always uses: leaves and nodes are
+    /// hashed with the `0x00` and `0x01` prefixes, and lone nodes are carried up rather than
+    /// duplicated, so this is the same as [`MerkleTree::construct`]. It merely names the
+    /// guarantee for callers that depend on it.
+    pub fn construct_rfc6962(input: &[Data]) -> MerkleTree<D> {
+        Self::construct(input)
+    }
+
+    /// Constructs a Merkle tree from borrowed leaf blobs, such as string slices or mapped
+    /// buffers, without copying them into owned [`Data`]
+    /// Produces the same tree as [`MerkleTree::construct`] over the equivalent owned data
+    pub fn construct_from_slices<T: AsRef<[u8]>>(input: &[T]) -> MerkleTree<D> {
+        Self::build(input, |item| hash_data::<D>(item)).unwrap()
+    }
+
+    /// Constructs a Merkle tree whose leaf hashes are computed by `leaf_hasher` instead of
+    /// the prefixed digest, e.g. to length-prefix leaves as some specifications require
+    /// Internal nodes are hashed as usual. The tree does not keep `leaf_hasher`, so methods
+    /// that hash new leaf data, such as [`MerkleTree::update`] or [`MerkleTree::push`], fail
+    /// with [`MerkleError::UnsupportedScheme`]. Length of the input must be nonzero
+    pub fn construct_with<F: Fn(&Data) -> Hash>(input: &[Data], leaf_hasher: F) -> MerkleTree<D> {
+        Self::build_in(HashScheme::Opaque, input, leaf_hasher).unwrap()
     }
 
-    fn branch(left: MerkleTree, right: MerkleTree) -> MerkleTree {
+    /// Constructs a Merkle tree over typed items, hashing the bytes that `to_bytes` turns each
+    /// of them into
+    /// Produces the same tree as [`MerkleTree::construct`] over the mapped data. Length of the
+    /// input must be nonzero
+    pub fn from_items<T, F: Fn(&T) -> Data>(items: &[T], to_bytes: F) -> MerkleTree<D> {
+        Self::build(items, |item| hash_data::<D>(to_bytes(item))).unwrap()
+    }
+
+    /// Constructs a Merkle tree whose leaves are records of several fields, each hashed with
+    /// [`record_hash`] so that field boundaries are part of the hash
+    /// Methods that hash new leaf data, which is not a record, fail with
+    /// [`MerkleError::UnsupportedScheme`]. Length of the input must be nonzero
+    pub fn construct_records(records: &[Vec<Data>]) -> MerkleTree<D> {
+        Self::build_in(HashScheme::Opaque, records, |record| {
+            record_hash::<D>(record)
+        })
+        .unwrap()
+    }
+
+    /// Constructs a Merkle tree from given leaf blobs whose branches hash the length of each
+    /// child hash before it, as `D(0x01 || len(left) || left || len(right) || right)` with
+    /// 64-bit little endian lengths
+    ///
+    /// Child hashes all have the digest's length, so the default scheme is already
+    /// unambiguous, and prefixing only costs 16 more hashed bytes per branch. It matters
+    /// to schemes that feed variable-length values into branch hashes. Proofs of these trees
+    /// verify with [`Proof::verify_prefixed`]. Methods that rehash branches, such as
+    /// [`MerkleTree::update`] or [`MerkleTree::validate`], keep using the prefixed scheme.
+    /// Length of the input must be nonzero
+    pub fn construct_prefixed(input: &[Data]) -> MerkleTree<D> {
+        Self::build_in(HashScheme::Prefixed, input, |item| hash_data::<D>(item)).unwrap()
+    }
+
+    /// Constructs a Merkle tree from given leaf blobs whose branches hash a fixed separator
+    /// byte between their children, as `D(0x01 || left || separator || right)`, to match
+    /// schemes of other implementations
+    /// Proofs of these trees verify with [`Proof::verify_with_separator`]. Like with
+    /// [`MerkleTree::construct_prefixed`], methods that rehash branches keep using the
+    /// separator. Length of the input must be nonzero, see [`MerkleTree::try_construct`]
+    pub fn construct_with_separator(input: &[Data], separator: u8) -> MerkleTree<D> {
+        let scheme = HashScheme::Separated(separator);
+        Self::build_in(scheme, input, |item| hash_data::<D>(item)).unwrap()
+    }
+
+    /// Constructs a Merkle tree from given leaf blobs like [`MerkleTree::construct`], passing
+    /// an [`Event`] to the callback for every leaf hashed and every branch created, e.g. to
+    /// collect metrics
+    /// The callback has no effect on the resulting tree. Length of the input must be nonzero,
+    /// see [`MerkleTree::try_construct`]
+    pub fn construct_instrumented<F: FnMut(Event)>(input: &[Data], mut cb: F) -> MerkleTree<D> {
+        check_leaf_count(input.len()).unwrap();
+
+        let leaves: Vec<_> = input
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let hash = hash_data::<D>(item);
+                cb(Event::LeafHashed(index));
+                hash
+            })
+            .collect();
+        Self::assemble(&leaves, &mut |left, right, level| {
+            let hash = hash_concat::<D>(left, right);
+            cb(Event::BranchCreated { level });
+            hash
+        })
+    }
+
+    /// Tree over the given leaf hashes, hashing each branch with the given closure, which is
+    /// also passed the level of the branch above the leaves
+    fn assemble(
+        leaves: &[Hash],
+        hash_branch: &mut impl FnMut(&Hash, &Hash, usize) -> Hash,
+    ) -> MerkleTree<D> {
+        if let [leaf] = leaves {
+            return MerkleTree::leaf(leaf.clone());
+        }
+
+        let (left, right) = leaves.split_at(split_point(leaves.len()));
+        let (left, right) = (
+            Self::assemble(left, hash_branch),
+            Self::assemble(right, hash_branch),
+        );
+        // The branch sits one level above its perfect left subtree
+        let level = leaves.len().next_power_of_two().trailing_zeros() as usize;
         MerkleTree {
-            hash: hash_concat(&left.hash, &right.hash),
+            hash: hash_branch(&left.hash, &right.hash, level),
             children: MerkleTreeChildren::Branch {
                 left: Box::new(left),
                 right: Box::new(right),
             },
+            scheme: HashScheme::Standard,
+            digest: PhantomData,
+        }
+    }
+
+    /// Constructs a Merkle tree from given leaf blobs, hashing each distinct blob only once
+    /// Produces exactly the same tree as [`MerkleTree::construct`], which pays off when the
+    /// input repeats large blobs. Length of the input must be nonzero
+    #[cfg(feature = "std")]
+    pub fn construct_dedup(input: &[Data]) -> MerkleTree<D> {
+        Self::build_memoized(input, |item| hash_data::<D>(item))
+    }
+
+    #[cfg(feature = "std")]
+    fn build_memoized(input: &[Data], leaf_hasher: impl Fn(&Data) -> Hash) -> MerkleTree<D> {
+        let mut cache = std::collections::HashMap::new();
+        let mut builder = Builder::new();
+        for item in input {
+            let hash = cache
+                .entry(item.as_slice())
+                .or_insert_with(|| leaf_hasher(item));
+            builder.push(MerkleTree::leaf(hash.clone()));
+        }
+
+        builder.finish().unwrap()
+    }
+
+    /// Constructs a perfect Merkle tree from given leaf blobs, appending copies of `fill` until
+    /// the leaf count is a power of two
+    /// The result is the same as [`MerkleTree::construct`] over the padded input, whose length
+    /// is `input.len().next_power_of_two()`. The fill is hashed only once. Length of the input
+    /// must be nonzero
+    pub fn construct_padded(input: &[Data], fill: Data) -> MerkleTree<D> {
+        check_leaf_count(input.len()).unwrap();
+
+        let mut builder = Builder::new();
+        for item in input {
+            builder.push(MerkleTree::leaf(hash_data::<D>(item)));
+        }
+        let fill = hash_data::<D>(&fill);
+        for _ in input.len()..input.len().next_power_of_two() {
+            builder.push(MerkleTree::leaf(fill.clone()));
+        }
+
+        builder.finish().unwrap()
+    }
+
+    /// Constructs a Merkle tree from given leaf blobs that keeps a copy of each blob in its
+    /// leaf, so that [`MerkleTree::get_leaf`] can serve the data behind a proof
+    /// Produces the same hashes as [`MerkleTree::construct`], which keeps no data. Length of
+    /// the input must be nonzero
+    pub fn construct_retaining(input: &[Data]) -> MerkleTree<D> {
+        let mut builder = Builder::new();
+        for item in input {
+            builder.push(MerkleTree {
+                hash: hash_data::<D>(item),
+                children: MerkleTreeChildren::Leaf {
+                    data: Some(item.clone()),
+                },
+                scheme: HashScheme::Standard,
+                digest: PhantomData,
+            });
+        }
+
+        builder.finish().unwrap()
+    }
+
+    /// Constructs a Merkle tree whose leaves are hashed with an HMAC under the given key, so
+    /// that identical leaves in trees with different keys cannot be linked by their hashes
+    /// Internal nodes are hashed as usual. The tree does not keep the key, so methods that hash
+    /// new leaf data, such as [`MerkleTree::update`] or [`MerkleTree::push`], fail with
+    /// [`MerkleError::UnsupportedScheme`]. Length of the input must be nonzero
+    pub fn construct_keyed(input: &[Data], key: &[u8]) -> MerkleTree<D>
+    where
+        D: BlockSizeUser + Clone,
+    {
+        use hmac::{Mac, SimpleHmac};
+
+        // HMAC accepts keys of any length. The key pads are derived once and the keyed state
+        // is cloned for every leaf
+        let keyed = SimpleHmac::<D>::new_from_slice(key).unwrap();
+        Self::construct_with(input, |data| {
+            keyed
+                .clone()
+                .chain_update([LEAF_PREFIX])
+                .chain_update(data)
+                .finalize()
+                .into_bytes()
+                .to_vec()
+        })
+    }
+
+    /// Constructs a perfect Merkle tree from given leaf blobs, padded with
+    /// [`MerkleTree::empty_leaf_hash`] sentinel leaves until the leaf count is a power of two
+    /// Unlike with [`MerkleTree::construct_padded`], the padding cannot be mistaken for any
+    /// present leaf. Subtrees of padding only take their hashes from an [`EmptyHashes`] table,
+    /// so padding costs no more hashing than the levels above the input. Length of the input
+    /// must be nonzero
+    pub fn construct_padded_empty(input: &[Data]) -> MerkleTree<D> {
+        check_leaf_count(input.len()).unwrap();
+
+        let leaves: Vec<_> = input.iter().map(hash_data::<D>).collect();
+        let height = input.len().next_power_of_two().trailing_zeros() as usize;
+        Self::pad_empty(&leaves, height, &EmptyHashes::new(height))
+    }
+
+    /// Perfect tree of the given height over the given leaf hashes, followed by empty leaves
+    fn pad_empty(leaves: &[Hash], height: usize, empty: &EmptyHashes<D>) -> MerkleTree<D> {
+        match (leaves, height) {
+            ([], _) => Self::empty_subtree(height, empty),
+            ([leaf], 0) => MerkleTree::leaf(leaf.clone()),
+            _ => {
+                let (left, right) = leaves.split_at(leaves.len().min(1 << (height - 1)));
+                MerkleTree::branch(
+                    Self::pad_empty(left, height - 1, empty),
+                    Self::pad_empty(right, height - 1, empty),
+                )
+            }
+        }
+    }
+
+    /// Perfect tree of the given height over empty leaves, without hashing anything
+    fn empty_subtree(height: usize, empty: &EmptyHashes<D>) -> MerkleTree<D> {
+        let hash = empty.subtree_hash(height).unwrap().clone();
+        if height == 0 {
+            return MerkleTree::leaf(hash);
+        }
+
+        let child = Self::empty_subtree(height - 1, empty);
+        MerkleTree {
+            hash,
+            children: MerkleTreeChildren::Branch {
+                left: Box::new(child.clone()),
+                right: Box::new(child),
+            },
+            scheme: HashScheme::Standard,
+            digest: PhantomData,
+        }
+    }
+
+    /// Constructs a Merkle tree whose leaves are salted with their position, hashing leaf `i`
+    /// as `hash_data(i as 64-bit little endian || data)`
+    /// Leaves then cannot be reordered without changing their hashes, even if proofs are not
+    /// bound to positions. Mutations salt new leaves with their index too. Length of the input
+    /// must be nonzero
+    pub fn construct_indexed(input: &[Data]) -> MerkleTree<D> {
+        let mut builder = Builder::new();
+        for (index, item) in input.iter().enumerate() {
+            let hash = hash_indexed::<D>(index, item);
+            builder.push(MerkleTree::leaf_in(HashScheme::Indexed, hash));
         }
+
+        builder.finish().unwrap()
     }
 
-    /// Constructs a Merkle tree from given leaf blobs
-    /// Length of the input must be a nonzero power of two
-    pub fn construct(input: &[Data]) -> MerkleTree {
-        assert!(input.len().is_power_of_two());
+    fn build<T>(
+        input: &[T],
+        leaf_hasher: impl Fn(&T) -> Hash,
+    ) -> Result<MerkleTree<D>, MerkleError> {
+        Self::build_in(HashScheme::Standard, input, leaf_hasher)
+    }
 
-        let depth = (input.len().trailing_zeros() + 1) as usize;
+    /// Builds a tree of the given scheme, whose leaf hashes `leaf_hasher` computes
+    fn build_in<T>(
+        scheme: HashScheme,
+        input: &[T],
+        leaf_hasher: impl Fn(&T) -> Hash,
+    ) -> Result<MerkleTree<D>, MerkleError> {
+        check_leaf_count(input.len())?;
 
-        // Unfinished subtrees that are waiting for corresponding right-side trees
-        let mut left_side: Vec<Option<MerkleTree>> = (0..depth).map(|_| None).collect();
+        let depth = (usize::BITS - input.len().leading_zeros()) as usize;
 
+        let mut builder = Builder::with_depth(depth, MerkleTree::branch);
         for item in input {
-            let mut right = MerkleTree::leaf(hash_data(item));
-            // Propagate and merge subtrees
-            for ls in left_side.iter_mut() {
-                // Merge with left-side node if it exists
-                if let Some(left) = ls.take() {
-                    right = MerkleTree::branch(left, right);
+            builder.push(MerkleTree::leaf_in(scheme, leaf_hasher(item)));
+        }
+
+        Ok(builder.finish().unwrap())
+    }
+
+    /// Constructs a Merkle tree from already hashed leaves, skipping the leaf hashing step
+    /// The hashes become leaf nodes as is, so any domain separation is up to the caller
+    /// Length of the input must be nonzero
+    pub fn from_leaf_hashes(hashes: &[Hash]) -> MerkleTree<D> {
+        let mut builder = Builder::new();
+        for hash in hashes {
+            builder.push(MerkleTree::leaf(hash.clone()));
+        }
+
+        builder.finish().unwrap()
+    }
+
+    /// Constructs a Merkle tree from given leaf blobs, hashing leaves and each level in parallel
+    /// Produces exactly the same tree as [`MerkleTree::construct`]. Length of the input must be
+    /// nonzero, see [`MerkleTree::try_construct`]
+    #[cfg(feature = "rayon")]
+    pub fn construct_parallel(input: &[Data]) -> MerkleTree<D> {
+        use rayon::prelude::*;
+
+        check_leaf_count(input.len()).unwrap();
+
+        let mut level: Vec<MerkleTree<D>> = input
+            .par_iter()
+            .map(|item| MerkleTree::leaf(hash_data::<D>(item)))
+            .collect();
+
+        // Pair adjacent nodes level by level, carrying a lone right-most node up unchanged
+        while level.len() > 1 {
+            level = level
+                .into_par_iter()
+                .chunks(2)
+                .map(|pair| {
+                    let mut pair = pair.into_iter();
+                    let left = pair.next().unwrap();
+                    match pair.next() {
+                        Some(right) => MerkleTree::branch(left, right),
+                        None => left,
+                    }
+                })
+                .collect();
+        }
+
+        level.pop().unwrap()
+    }
+
+    /// Constructs a Merkle tree from given leaf blobs, also recording the root of every
+    /// power of two prefix of the input as `(leaf count, root hash)`, e.g. for progress reports
+    /// Each prefix is complete as soon as its last leaf is pushed, when all its leaves have
+    /// merged into a single pending subtree. Its hash is that of [`MerkleTree::construct`]
+    /// over the prefix. Length of the input must be nonzero
+    pub fn construct_with_checkpoints(input: &[Data]) -> (MerkleTree<D>, Vec<(usize, Hash)>) {
+        let mut checkpoints = Vec::new();
+        let mut builder = Builder::new();
+        for (index, item) in input.iter().enumerate() {
+            builder.push(MerkleTree::leaf(hash_data::<D>(item)));
+
+            let count = index + 1;
+            if count.is_power_of_two() {
+                let level = count.trailing_zeros() as usize;
+                let prefix = &builder.left_side[level][0];
+                checkpoints.push((count, prefix.hash.clone()));
+            }
+        }
+
+        (builder.finish().unwrap(), checkpoints)
+    }
+
+    /// Constructs a Merkle tree from leaf blobs yielded by a fallible source, such as chunks
+    /// read one by one, stopping at the first error
+    /// A source that yields no leaves gives `Ok(None)`, as there is no tree to build
+    pub fn try_from_results<E, I: IntoIterator<Item = Result<Data, E>>>(
+        iter: I,
+    ) -> Result<Option<MerkleTree<D>>, E> {
+        let mut builder = Builder::new();
+        for item in iter {
+            builder.push(MerkleTree::leaf(hash_data::<D>(item?)));
+        }
+
+        Ok(builder.finish())
+    }
+
+    /// Constructs a Merkle tree from leaf blobs yielded by an async stream, hashing each one
+    /// as it arrives
+    /// Produces the same tree as [`MerkleTree::construct`]. The stream must yield at least
+    /// one leaf
+    #[cfg(feature = "async")]
+    pub async fn construct_from_stream<S: futures_util::Stream<Item = Data>>(
+        stream: S,
+    ) -> MerkleTree<D> {
+        use futures_util::StreamExt;
+
+        let mut stream = core::pin::pin!(stream);
+        let mut builder = Builder::new();
+        while let Some(item) = stream.next().await {
+            builder.push(MerkleTree::leaf(hash_data::<D>(&item)));
+        }
+
+        builder.finish().unwrap()
+    }
+
+    /// Constructs a Merkle tree over consecutive `chunk_size` byte blocks read from `reader`,
+    /// hashing each block as it arrives
+    /// The last block may be shorter; an empty reader or a zero `chunk_size` is an
+    /// `InvalidInput` error
+    #[cfg(feature = "std")]
+    pub fn construct_from_reader<R: std::io::Read>(
+        mut reader: R,
+        chunk_size: usize,
+    ) -> std::io::Result<MerkleTree<D>> {
+        use std::io::{Error, ErrorKind, Read};
+
+        if chunk_size == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "chunk size must be nonzero",
+            ));
+        }
+
+        let mut builder = Builder::new();
+        let mut chunk = Vec::with_capacity(chunk_size);
+        loop {
+            chunk.clear();
+            // Short reads are retried until the block is full or the reader runs dry
+            reader
+                .by_ref()
+                .take(chunk_size as u64)
+                .read_to_end(&mut chunk)?;
+            if chunk.is_empty() {
+                break;
+            }
+            builder.push(MerkleTree::leaf(hash_data::<D>(&chunk)));
+        }
+
+        builder
+            .finish()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "no data to construct a tree from"))
+    }
+
+    /// Appends a single leaf, rehashing only the nodes along the right edge of the tree
+    /// Fails with [`MerkleError::UnsupportedScheme`] if the tree cannot hash leaf data, see
+    /// [`MerkleTree::construct_with`]
+    pub fn push(&mut self, data: Data) -> Result<(), MerkleError> {
+        self.extend(core::slice::from_ref(&data))
+    }
+
+    /// Appends a single leaf like [`MerkleTree::push`], returning the new root hash and an
+    /// inclusion proof of the appended leaf against it, as an append-only log would hand out
+    pub fn append_proved(&mut self, data: Data) -> Result<(Hash, Proof<D>), MerkleError> {
+        self.push(data)?;
+        let proof = self.prove(self.leaf_count() - 1).unwrap();
+        Ok((self.hash.clone(), proof))
+    }
+
+    /// Appends several leaves at once, splitting the tree into its pending subtrees only once
+    /// The result is the same as pushing the leaves one by one. Fails like
+    /// [`MerkleTree::push`], leaving the tree unchanged
+    pub fn extend(&mut self, items: &[Data]) -> Result<(), MerkleError> {
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let (scheme, start) = (self.scheme, self.leaf_count());
+        check_leaf_count(start.saturating_add(items.len()))?;
+        let hashes = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| scheme.hash_leaf::<D>(start + i, item))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Temporarily take ownership of the tree to split it into its pending subtrees
+        let tree = mem::replace(self, MerkleTree::leaf(Hash::new()));
+
+        let mut builder = Builder::from_tree(tree);
+        for hash in hashes {
+            builder.push(MerkleTree::leaf_in(scheme, hash));
+        }
+
+        *self = builder.finish().unwrap();
+        Ok(())
+    }
+
+    /// Returns a tree over only the first `new_len` leaves, rehashing only the nodes along its
+    /// new right edge
+    /// `new_len` must be nonzero and at most the current leaf count
+    pub fn truncate(&self, new_len: usize) -> MerkleTree<D> {
+        assert!(new_len > 0 && new_len <= self.leaf_count());
+        self.prefix(new_len)
+    }
+
+    /// Tree over the first `len` leaves of this subtree
+    fn prefix(&self, len: usize) -> MerkleTree<D> {
+        match &self.children {
+            MerkleTreeChildren::Branch { left, right } => {
+                // The left subtree of any prefix longer than it stays the same
+                let left_count = left.leaf_count();
+                if len <= left_count {
+                    left.prefix(len)
                 } else {
-                    *ls = Some(right);
-                    break;
+                    Self::branch((**left).clone(), right.prefix(len - left_count))
                 }
             }
+            MerkleTreeChildren::Leaf { .. } => self.clone(),
+        }
+    }
+
+    /// Replaces the leaf at the given index, rehashing only the path from it to the root
+    /// Fails with [`MerkleError::UnsupportedScheme`] if the tree cannot hash leaf data, see
+    /// [`MerkleTree::construct_with`]
+    pub fn update(&mut self, index: usize, new_data: Data) -> Result<(), MerkleError> {
+        let len = self.leaf_count();
+        if index >= len {
+            return Err(MerkleError::IndexOutOfRange { index, len });
         }
 
-        // The topmost node is root of the merkle tree
-        left_side.pop().unwrap().unwrap()
+        self.set_leaf(index, new_data)
     }
 
-    /// Verifies that the given input data produces the given root hash
-    pub fn verify(input: &[Data], root_hash: &Hash) -> bool {
-        MerkleTree::construct(input).hash == *root_hash
+    /// Removes the leaf at the given index, replacing its hash with the
+    /// [`MerkleTree::empty_leaf_hash`] tombstone and rehashing its ancestors, so the leaf count
+    /// stays the same
+    /// Proofs of the other leaves must be regenerated against the new root. A retained copy of
+    /// the data is dropped
+    pub fn remove(&mut self, index: usize) -> Result<(), MerkleError> {
+        let len = self.leaf_count();
+        if index >= len {
+            return Err(MerkleError::IndexOutOfRange { index, len });
+        }
+
+        self.replace_leaf(index, Self::empty_leaf_hash(), None);
+        Ok(())
     }
-}
 
-fn hash_data(data: &Data) -> Hash {
-    sha2::Sha256::digest(data).to_vec()
-}
+    /// Replaces the leaf at an in-range index and rehashes its ancestors
+    /// A retained copy of the old data is replaced by the new data
+    fn set_leaf(&mut self, index: usize, new_data: Data) -> Result<(), MerkleError> {
+        let hash = self.scheme.hash_leaf::<D>(index, &new_data)?;
+        self.replace_leaf(index, hash, Some(new_data));
+        Ok(())
+    }
 
-fn hash_concat(h1: &Hash, h2: &Hash) -> Hash {
-    let h3 = h1.iter().chain(h2).copied().collect();
-    hash_data(&h3)
-}
+    /// Sets the hash of the leaf at an in-range index and rehashes its ancestors
+    /// Retained data of the leaf is replaced by `new_data`
+    fn replace_leaf(&mut self, index: usize, hash: Hash, new_data: Option<Data>) {
+        match &mut self.children {
+            MerkleTreeChildren::Leaf { data } => {
+                self.hash = hash;
+                if data.is_some() {
+                    *data = new_data;
+                }
+            }
+            MerkleTreeChildren::Branch { left, right } => {
+                let left_count = left.leaf_count();
+                if index < left_count {
+                    left.replace_leaf(index, hash, new_data);
+                } else {
+                    right.replace_leaf(index - left_count, hash, new_data);
+                }
+                self.hash = self.scheme.hash_branch::<D>(&left.hash, &right.hash);
+            }
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::{hash_concat, hash_data, MerkleTree};
+    /// Root hash of the tree
+    pub fn root(&self) -> &Hash {
+        &self.hash
+    }
 
-    #[test]
-    fn manual_hash_calculation() {
-        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+    /// Children of the root node, for traversing the tree by hand
+    pub fn children(&self) -> &MerkleTreeChildren<D> {
+        &self.children
+    }
 
-        let mt = MerkleTree::construct(&input);
+    /// Left child of the root node, or `None` for a leaf
+    pub fn left(&self) -> Option<&MerkleTree<D>> {
+        match &self.children {
+            MerkleTreeChildren::Leaf { .. } => None,
+            MerkleTreeChildren::Branch { left, .. } => Some(left),
+        }
+    }
 
-        let a = hash_data(&input[0]);
-        let b = hash_data(&input[1]);
-        let c = hash_data(&input[2]);
-        let d = hash_data(&input[3]);
+    /// Right child of the root node, or `None` for a leaf
+    pub fn right(&self) -> Option<&MerkleTree<D>> {
+        match &self.children {
+            MerkleTreeChildren::Leaf { .. } => None,
+            MerkleTreeChildren::Branch { right, .. } => Some(right),
+        }
+    }
 
-        let ab = hash_concat(&a, &b);
-        let cd = hash_concat(&c, &d);
+    /// Root hash of the tree as a lowercase hex string
+    pub fn root_hex(&self) -> String {
+        to_hex(&self.hash)
+    }
 
-        let abcd = hash_concat(&ab, &cd);
+    /// Iterates over the leaf hashes from left to right
+    pub fn leaves(&self) -> impl Iterator<Item = &Hash> {
+        Leaves {
+            stack: alloc::vec![self],
+        }
+    }
 
-        assert_eq!(abcd, mt.hash);
+    /// Iterates over the hashes of all nodes in breadth-first order: the root, then the nodes
+    /// one level below it from left to right, and so on
+    /// Leaves carried up from the right edge come out at the level they are reached at.
+    pub fn iter_bfs(&self) -> impl Iterator<Item = &Hash> {
+        BreadthFirst {
+            queue: VecDeque::from([self]),
+        }
+    }
 
-        assert!(MerkleTree::verify(&input, &mt.hash));
+    /// Hashes of the nodes at the given depth below the root, `0` for the root itself, from
+    /// left to right
+    /// Leaves carried up from the right edge only appear at the level they are reached at, so
+    /// the deepest level, `depth() - 1`, holds all leaves only in perfect trees. Levels below
+    /// the deepest one are empty.
+    pub fn level_hashes(&self, level: usize) -> Vec<Hash> {
+        let mut hashes = Vec::new();
+        self.collect_level(level, &mut hashes);
+        hashes
     }
 
-    #[test]
-    fn different_sizes() {
-        for size in 1..10 {
-            let input: Vec<_> = (0..(1 << size)).map(|i| vec![i as u8]).collect();
+    /// Collects the hashes of the nodes `level` levels below this one
+    fn collect_level(&self, level: usize, hashes: &mut Vec<Hash>) {
+        match (&self.children, level) {
+            (_, 0) => hashes.push(self.hash.clone()),
+            (MerkleTreeChildren::Branch { left, right }, _) => {
+                left.collect_level(level - 1, hashes);
+                right.collect_level(level - 1, hashes);
+            }
+            (MerkleTreeChildren::Leaf { .. }, _) => {}
+        }
+    }
+
+    /// Node hashes in heap order, where the children of node `i` are nodes `2i + 1` and
+    /// `2i + 2`, see [`MerkleTree::from_flat`]
+    /// Returns `None` if the tree does not fit this layout without gaps. Trees with a power of
+    /// two of leaves always fit, but e.g. the carried leaf of a 5-leaf tree leaves a gap on
+    /// the level below it.
+    pub fn to_flat(&self) -> Option<Vec<Hash>> {
+        let len = self.node_count();
+        let mut flat = alloc::vec![Vec::new(); len];
+
+        let mut stack = alloc::vec![(self, 0)];
+        while let Some((node, position)) = stack.pop() {
+            *flat.get_mut(position)? = node.hash.clone();
+            if let MerkleTreeChildren::Branch { left, right } = &node.children {
+                stack.push((right, 2 * position + 2));
+                stack.push((left, 2 * position + 1));
+            }
+        }
+        Some(flat)
+    }
+
+    /// Rebuilds a tree from its node hashes in heap order, as produced by
+    /// [`MerkleTree::to_flat`]
+    /// A tree of `n` leaves has `2n - 1` nodes. The hashes are taken as is, see
+    /// [`MerkleTree::validate`]
+    pub fn from_flat(flat: &[Hash]) -> Result<MerkleTree<D>, MerkleError> {
+        if flat.is_empty() {
+            return Err(MerkleError::EmptyInput);
+        }
+        if flat.len().is_multiple_of(2) {
+            return Err(MerkleError::LengthMismatch {
+                expected: flat.len() + 1,
+                actual: flat.len(),
+            });
+        }
+
+        Self::from_flat_at(flat, 0).map(|(tree, _)| tree)
+    }
 
-            let mt = MerkleTree::construct(&input);
-            assert!(MerkleTree::verify(&input, &mt.hash));
+    /// Subtree rooted at the given position of a heap ordered tree, with its leaf count
+    fn from_flat_at(flat: &[Hash], position: usize) -> Result<(MerkleTree<D>, usize), MerkleError> {
+        if 2 * position + 1 >= flat.len() {
+            return Ok((MerkleTree::leaf(flat[position].clone()), 1));
         }
+
+        let (left, left_count) = Self::from_flat_at(flat, 2 * position + 1)?;
+        let (right, right_count) = Self::from_flat_at(flat, 2 * position + 2)?;
+        // Leaf counts and proofs rely on every left subtree being perfect
+        if left_count != 1 << (left.depth() - 1) {
+            return Err(MerkleError::NotPowerOfTwo(left_count));
+        }
+
+        let tree = MerkleTree {
+            hash: flat[position].clone(),
+            children: MerkleTreeChildren::Branch {
+                left: Box::new(left),
+                right: Box::new(right),
+            },
+            scheme: HashScheme::Standard,
+            digest: PhantomData,
+        };
+        Ok((tree, left_count + right_count))
+    }
+
+    /// Iterates over leaf hashes along with their indices, from left to right
+    pub fn enumerate_leaves(&self) -> impl Iterator<Item = (usize, &Hash)> {
+        self.leaves().enumerate()
+    }
+
+    /// Finds the index of the first leaf holding the given data
+    /// Identical leaves hash identically, so later duplicates are never reported
+    pub fn index_of(&self, data: &Data) -> Option<usize> {
+        let hash = hash_data::<D>(data);
+        self.leaves().position(|leaf| *leaf == hash)
+    }
+
+    /// Whether any leaf holds the given data
+    pub fn contains(&self, data: &Data) -> bool {
+        self.index_of(data).is_some()
+    }
+
+    /// Data of the leaf at the given index, if the tree retains it
+    /// Returns `None` if the index is out of range or the leaf keeps no data, see
+    /// [`MerkleTree::construct_retaining`]
+    pub fn get_leaf(&self, index: usize) -> Option<&Data> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+
+        let mut node = self;
+        let mut index = index;
+        while let MerkleTreeChildren::Branch { left, right } = &node.children {
+            let left_count = left.leaf_count();
+            if index < left_count {
+                node = left;
+            } else {
+                index -= left_count;
+                node = right;
+            }
+        }
+
+        match &node.children {
+            MerkleTreeChildren::Leaf { data } => data.as_ref(),
+            MerkleTreeChildren::Branch { .. } => unreachable!(),
+        }
+    }
+
+    /// Lists the indices of leaves that differ between two trees, in ascending order
+    ///
+    /// Only subtrees whose hashes disagree are descended into. Trees with different leaf
+    /// counts have different shapes, so every index of the larger tree is reported then.
+    pub fn diff(&self, other: &MerkleTree<D>) -> Vec<usize> {
+        let len = self.leaf_count();
+        let other_len = other.leaf_count();
+        if len != other_len {
+            return (0..len.max(other_len)).collect();
+        }
+
+        let mut indices = Vec::new();
+        self.collect_diff(other, 0, &mut indices);
+        indices
+    }
+
+    /// Collects differing leaf indices of two equally shaped subtrees
+    /// `offset` is the index of the subtrees' first leaf
+    fn collect_diff(&self, other: &MerkleTree<D>, offset: usize, indices: &mut Vec<usize>) {
+        if self.hash == other.hash {
+            return;
+        }
+
+        match (&self.children, &other.children) {
+            (
+                MerkleTreeChildren::Branch { left, right },
+                MerkleTreeChildren::Branch {
+                    left: other_left,
+                    right: other_right,
+                },
+            ) => {
+                left.collect_diff(other_left, offset, indices);
+                right.collect_diff(other_right, offset + left.leaf_count(), indices);
+            }
+            _ => indices.push(offset),
+        }
+    }
+
+    /// Finds the deepest node of this tree that covers all differences to `other`, along with
+    /// its path from the root, or `None` if the roots match
+    ///
+    /// This is the smallest subtree a peer has to request to reconcile the two trees. It is the
+    /// shallowest node whose children both differ, or a differing leaf. Trees with different
+    /// leaf counts have different shapes, so the whole tree is reported then.
+    pub fn first_diff_subtree<'a>(
+        &'a self,
+        other: &'a MerkleTree<D>,
+    ) -> Option<(Vec<bool>, &'a MerkleTree<D>)> {
+        if self.hash == other.hash {
+            return None;
+        }
+
+        let mut path = Vec::new();
+        let mut node = self;
+        if self.leaf_count() == other.leaf_count() {
+            let mut other = other;
+            while let (
+                MerkleTreeChildren::Branch { left, right },
+                MerkleTreeChildren::Branch {
+                    left: other_left,
+                    right: other_right,
+                },
+            ) = (&node.children, &other.children)
+            {
+                // Step down only while all differences are on the same side
+                let (is_right, next, other_next) =
+                    match (left.hash != other_left.hash, right.hash != other_right.hash) {
+                        (true, false) => (false, left, other_left),
+                        (false, true) => (true, right, other_right),
+                        _ => break,
+                    };
+                path.push(is_right);
+                node = next;
+                other = other_next;
+            }
+        }
+
+        Some((path, node))
+    }
+
+    /// Root hash of the tree as padded base64
+    pub fn root_base64(&self) -> String {
+        to_base64(&self.hash)
+    }
+
+    /// Hash of the node reached by following `path` from the root
+    /// `false` descends to the left child and `true` to the right one
+    /// Returns `None` if the path runs past a leaf
+    pub fn node_hash(&self, path: &[bool]) -> Option<&Hash> {
+        self.node(path).map(|node| &node.hash)
+    }
+
+    /// Owned copy of the subtree rooted at the node reached by following `path`, see
+    /// [`MerkleTree::node_hash`]
+    pub fn subtree(&self, path: &[bool]) -> Option<MerkleTree<D>> {
+        self.node(path).cloned()
+    }
+
+    /// Root hashes of the consecutive groups of `leaves_per_piece` leaves, from left to right
+    /// Each is the root of a subtree, the same as that of a tree built over just its group.
+    /// `leaves_per_piece` must be a power of two dividing the leaf count
+    pub fn piece_roots(&self, leaves_per_piece: usize) -> Vec<Hash> {
+        assert!(
+            leaves_per_piece.is_power_of_two()
+                && self.leaf_count().is_multiple_of(leaves_per_piece)
+        );
+
+        // The left subtree of every larger branch holds a multiple of the piece length
+        let mut roots = Vec::with_capacity(self.leaf_count() / leaves_per_piece);
+        let mut stack = alloc::vec![self];
+        while let Some(node) = stack.pop() {
+            match &node.children {
+                MerkleTreeChildren::Branch { left, right }
+                    if node.leaf_count() > leaves_per_piece =>
+                {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                _ => roots.push(node.hash.clone()),
+            }
+        }
+        roots
+    }
+
+    /// Node reached by following `path` from the root, see [`MerkleTree::node_hash`]
+    fn node(&self, path: &[bool]) -> Option<&MerkleTree<D>> {
+        path.iter()
+            .try_fold(self, |node, &is_right| match &node.children {
+                MerkleTreeChildren::Leaf { .. } => None,
+                MerkleTreeChildren::Branch { left, right } => {
+                    Some(if is_right { right } else { left }.as_ref())
+                }
+            })
+    }
+
+    /// Checks that every branch hash is the hash of its children, e.g. after loading a tree from
+    /// untrusted storage
+    /// Leaf hashes cannot be checked without their data, so they are taken as is
+    pub fn validate(&self) -> bool {
+        // Walk with an explicit stack, so that the check itself cannot run out of call stack
+        // however the tree was shaped
+        let mut stack = alloc::vec![self];
+        while let Some(node) = stack.pop() {
+            if let MerkleTreeChildren::Branch { left, right } = &node.children {
+                let schemes_match = left.scheme == node.scheme && right.scheme == node.scheme;
+                if !schemes_match
+                    || node.hash != node.scheme.hash_branch::<D>(&left.hash, &right.hash)
+                {
+                    return false;
+                }
+                stack.push(right);
+                stack.push(left);
+            }
+        }
+        true
+    }
+
+    /// Estimated number of heap and inline bytes held by this (sub)tree
+    /// This counts the node structs, the boxes of their children and the hash and retained
+    /// data buffers by their capacity, but not any allocator overhead
+    pub fn memory_size(&self) -> usize {
+        let own = mem::size_of::<Self>() + self.hash.capacity();
+        match &self.children {
+            MerkleTreeChildren::Leaf { data } => own + data.as_ref().map_or(0, Vec::capacity),
+            // The children themselves live in their boxes
+            MerkleTreeChildren::Branch { left, right } => {
+                own + left.memory_size() + right.memory_size()
+            }
+        }
+    }
+
+    /// Number of leaves in this (sub)tree
+    pub fn leaf_count(&self) -> usize {
+        match &self.children {
+            MerkleTreeChildren::Leaf { .. } => 1,
+            // The left subtree is always perfect, so its size follows from its depth
+            MerkleTreeChildren::Branch { left, right } => {
+                (1 << (left.depth() - 1)) + right.leaf_count()
+            }
+        }
+    }
+
+    /// Number of nodes in this (sub)tree, leaves and branches alike
+    pub fn node_count(&self) -> usize {
+        self.leaf_count() + self.internal_count()
+    }
+
+    /// Number of branch nodes in this (sub)tree
+    /// Every branch joins two subtrees, so there is one less of them than of leaves
+    pub fn internal_count(&self) -> usize {
+        self.leaf_count() - 1
+    }
+
+    /// Number of sibling hashes in the longest inclusion proof of the tree, one less than its
+    /// depth, e.g. to allocate buffers for proofs
+    /// Every proof of a tree with a power of two of leaves has this length. Otherwise the
+    /// right-most leaves are carried up past some levels and have shorter proofs.
+    pub fn proof_len(&self) -> usize {
+        self.depth() - 1
+    }
+
+    /// Number of levels from this node down to its deepest leaf, inclusive
+    /// A single leaf has depth 1
+    pub fn depth(&self) -> usize {
+        match &self.children {
+            MerkleTreeChildren::Leaf { .. } => 1,
+            // The left subtree is never shallower than the right one
+            MerkleTreeChildren::Branch { left, .. } => left.depth() + 1,
+        }
+    }
+
+    /// Whether all leaves are at the same depth, which is the case exactly when the leaf count
+    /// is a power of two
+    pub fn is_perfect(&self) -> bool {
+        self.perfect_depth().is_some()
+    }
+
+    /// Depth of the tree if all its leaves are at the same depth
+    fn perfect_depth(&self) -> Option<usize> {
+        match &self.children {
+            MerkleTreeChildren::Leaf { .. } => Some(1),
+            MerkleTreeChildren::Branch { left, right } => {
+                let depth = left.perfect_depth()?;
+                (right.perfect_depth()? == depth).then_some(depth + 1)
+            }
+        }
+    }
+
+    /// Generates an inclusion proof for the leaf at the given index
+    /// Returns `None` if the index is out of range
+    ///
+    /// The proof is tied to the position of the leaf rather than its data, so duplicate leaves
+    /// get distinct proofs that each verify for the same data.
+    pub fn prove(&self, index: usize) -> Option<Proof<D>> {
+        self.try_prove(index).ok()
+    }
+
+    /// Bundles the inclusion proof for the leaf at the given index with the index and, if the
+    /// tree retains it, the leaf data
+    /// Returns `None` if the index is out of range
+    pub fn prove_data(&self, index: usize) -> Option<ProvenLeaf<D>> {
+        let proof = self.prove(index)?;
+        Some(ProvenLeaf {
+            index,
+            data: self.get_leaf(index).cloned(),
+            proof,
+        })
+    }
+
+    /// Generates an inclusion proof for the leaf at the given index, like [`MerkleTree::prove`],
+    /// reporting why none could be generated
+    pub fn try_prove(&self, index: usize) -> Result<Proof<D>, ProofError> {
+        let levels = self.prove_iter(index).ok_or(ProofError::IndexOutOfRange {
+            index,
+            len: self.leaf_count(),
+        })?;
+        let (directions, siblings) = levels
+            .map(|(is_right, sibling)| (is_right, sibling.clone()))
+            .unzip();
+
+        Ok(Proof {
+            siblings,
+            directions,
+            digest: PhantomData,
+        })
+    }
+
+    /// Verifies that the given leaf data belongs to this tree by the given proof
+    pub fn verify_proof(&self, leaf: &Data, proof: &Proof<D>) -> bool {
+        proof.verify(leaf, self.root())
+    }
+
+    /// Lazily yields the `(direction, sibling)` pairs of the inclusion proof for the leaf at
+    /// the given index, from the leaf level up, without collecting them
+    /// Returns `None` if the index is out of range
+    pub fn prove_iter(&self, index: usize) -> Option<impl Iterator<Item = (bool, &Hash)>> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+
+        // Record the turns from the root down, the first one in the most significant bit
+        let mut turns = 0;
+        let mut levels = 0;
+        let mut node = self;
+        let mut index = index;
+        while let MerkleTreeChildren::Branch { left, right } = &node.children {
+            let left_count = left.leaf_count();
+            let is_right = index >= left_count;
+            if is_right {
+                index -= left_count;
+                node = right;
+            } else {
+                node = left;
+            }
+            turns = turns << 1 | usize::from(is_right);
+            levels += 1;
+        }
+
+        Some(ProofIter {
+            root: self,
+            turns,
+            levels,
+        })
+    }
+
+    /// Hashes of the nodes on the way from the root down to the leaf at the given index, both
+    /// inclusive
+    /// Returns `None` if the index is out of range
+    pub fn path_to_leaf(&self, index: usize) -> Option<Vec<Hash>> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+
+        let mut path = alloc::vec![self.hash.clone()];
+
+        let mut node = self;
+        let mut index = index;
+        while let MerkleTreeChildren::Branch { left, right } = &node.children {
+            let left_count = left.leaf_count();
+            if index < left_count {
+                node = left;
+            } else {
+                index -= left_count;
+                node = right;
+            }
+            path.push(node.hash.clone());
+        }
+
+        Some(path)
+    }
+
+    /// Constructs a Merkle tree along with the inclusion proofs of all of its leaves
+    /// Proof `i` proves `input[i]`, the proofs are collected in a single traversal
+    pub fn construct_with_proofs(input: &[Data]) -> (MerkleTree<D>, Vec<Proof<D>>) {
+        let tree = Self::construct(input);
+
+        let mut proofs = Vec::with_capacity(input.len());
+        tree.collect_proofs(&mut Vec::new(), &mut proofs);
+
+        (tree, proofs)
+    }
+
+    /// Pushes the proofs of all leaves below this node, given the siblings above it
+    fn collect_proofs<'a>(&'a self, path: &mut Vec<(&'a Hash, bool)>, proofs: &mut Vec<Proof<D>>) {
+        match &self.children {
+            MerkleTreeChildren::Leaf { .. } => proofs.push(Proof {
+                siblings: path.iter().rev().map(|&(hash, _)| hash.clone()).collect(),
+                directions: path.iter().rev().map(|&(_, is_right)| is_right).collect(),
+                digest: PhantomData,
+            }),
+            MerkleTreeChildren::Branch { left, right } => {
+                path.push((&right.hash, false));
+                left.collect_proofs(path, proofs);
+                path.pop();
+
+                path.push((&left.hash, true));
+                right.collect_proofs(path, proofs);
+                path.pop();
+            }
+        }
+    }
+
+    /// Length in bytes of every hash in the tree, and of every sibling in its proofs
+    pub fn hash_len() -> usize {
+        <D as Digest>::output_size()
+    }
+
+    /// Canonical root hash of an empty tree, which is the digest of no data at all
+    ///
+    /// This follows RFC 6962, and cannot collide with any leaf or node hash since those are
+    /// always prefixed. No tree is ever built for empty input, as it would have no leaves to
+    /// prove; use [`MerkleTree::root_or_empty`] to get a root for possibly empty data.
+    pub fn empty_root() -> Hash {
+        D::digest([]).to_vec()
+    }
+
+    /// Root hash of the tree over the given leaf blobs, or [`MerkleTree::empty_root`] if there
+    /// are none
+    pub fn root_or_empty(input: &[Data]) -> Hash {
+        if input.is_empty() {
+            return Self::empty_root();
+        }
+        Self::construct(input).hash
+    }
+
+    /// Sentinel hash standing for an absent leaf, which is all zero bytes
+    ///
+    /// Unlike the hash of zero-length data, `hash_data(&[])`, this is not the digest of any
+    /// input, so a genuinely empty position can never be mistaken for a present empty leaf.
+    /// [`SparseMerkleTree`] fills absent keys with it, and
+    /// [`MerkleTree::construct_padded_empty`] pads with it.
+    pub fn empty_leaf_hash() -> Hash {
+        alloc::vec![0; <D as Digest>::output_size()]
+    }
+
+    /// Verifies that the given input data produces the given root hash
+    pub fn verify(input: &[Data], root_hash: &Hash) -> bool {
+        ct_eq(&Self::construct(input).hash, root_hash)
+    }
+
+    /// Whether the tree has the given root hash, compared in constant time
+    pub fn matches_root(&self, root_hash: &Hash) -> bool {
+        ct_eq(&self.hash, root_hash)
+    }
+
+    /// Verifies that an already built tree has the given root hash, like
+    /// [`MerkleTree::verify`] without rebuilding it from its input
+    pub fn verify_tree(tree: &MerkleTree<D>, root_hash: &Hash) -> bool {
+        tree.matches_root(root_hash)
+    }
+
+    /// Verifies that the given input data of the committed length produces the given root hash
+    /// Fails early with [`MerkleError::LengthMismatch`] if the input has another length, such
+    /// as when a leaf was dropped by mistake
+    pub fn verify_sized(
+        input: &[Data],
+        expected_len: usize,
+        root_hash: &Hash,
+    ) -> Result<bool, MerkleError> {
+        if input.len() != expected_len {
+            return Err(MerkleError::LengthMismatch {
+                expected: expected_len,
+                actual: input.len(),
+            });
+        }
+
+        Ok(Self::verify_root(input, root_hash))
+    }
+
+    /// Verifies that the given input data produces the given root hash, like
+    /// [`MerkleTree::verify`], but only keeps `O(log n)` pending hashes instead of a whole tree
+    /// Empty input never verifies
+    pub fn verify_root(input: &[Data], root_hash: &Hash) -> bool {
+        let mut verifier = StreamVerifier::<D>::new();
+        for item in input {
+            verifier.update_ref(item);
+        }
+        verifier.finalize(root_hash)
+    }
+
+    /// Verifies that the leaves yielded by the iterator produce the given root hash, like
+    /// [`MerkleTree::verify_root`], without collecting them first
+    /// No leaves never verify
+    pub fn verify_iter<I: IntoIterator<Item = Data>>(iter: I, root_hash: &Hash) -> bool {
+        let mut verifier = StreamVerifier::<D>::new();
+        for leaf in iter {
+            verifier.update(leaf);
+        }
+        verifier.finalize(root_hash)
+    }
+}
+
+impl<D: Digest> FromIterator<Data> for MerkleTree<D> {
+    /// Constructs a Merkle tree from leaf blobs as they are yielded, without collecting them
+    /// The iterator must yield at least one item
+    fn from_iter<I: IntoIterator<Item = Data>>(iter: I) -> Self {
+        let mut builder = Builder::new();
+        for item in iter {
+            builder.push(MerkleTree::leaf(hash_data::<D>(&item)));
+        }
+
+        builder.finish().unwrap()
+    }
+}
+
+/// Renders the tree one node per line, indented by depth, with the first 8 hex characters of
+/// each node's hash
+impl<D> fmt::Display for MerkleTree<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_level(f, 0)
+    }
+}
+
+impl<D> MerkleTree<D> {
+    /// Writes this node and its descendants, indented as if `level` steps below the root
+    fn fmt_level(&self, f: &mut fmt::Formatter, level: usize) -> fmt::Result {
+        let prefix = to_hex(&self.hash[..self.hash.len().min(4)]);
+        match &self.children {
+            MerkleTreeChildren::Leaf { .. } => {
+                writeln!(f, "{:indent$}leaf {}", "", prefix, indent = 2 * level)
+            }
+            MerkleTreeChildren::Branch { left, right } => {
+                writeln!(f, "{:indent$}branch {}", "", prefix, indent = 2 * level)?;
+                left.fmt_level(f, level + 1)?;
+                right.fmt_level(f, level + 1)
+            }
+        }
+    }
+}
+
+// The standard traits are implemented by hand, since deriving them would require the digest
+// type itself to implement them
+
+impl<D> fmt::Debug for MerkleTree<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MerkleTree")
+            .field("hash", &self.hash)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+impl<D> Clone for MerkleTree<D> {
+    fn clone(&self) -> Self {
+        MerkleTree {
+            hash: self.hash.clone(),
+            children: self.children.clone(),
+            scheme: self.scheme,
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialEq for MerkleTree<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.scheme == other.scheme && self.children == other.children
+    }
+}
+
+impl<D> Eq for MerkleTree<D> {}
+
+/// Hashes only the root hash of the tree
+///
+/// This is consistent with equality, which also compares the root hashes among the rest of
+/// the tree. Two equal trees always hash alike, and trees with the same root but different
+/// contents can only come from a collision of the digest.
+impl<D> core::hash::Hash for MerkleTree<D> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        core::hash::Hash::hash(&self.hash, state);
+    }
+}
+
+/// Compares the root hash of the tree
+impl<D> PartialEq<Hash> for MerkleTree<D> {
+    fn eq(&self, other: &Hash) -> bool {
+        self.hash == *other
+    }
+}
+
+/// Compares against the root hash of the tree
+impl<D> PartialEq<MerkleTree<D>> for Hash {
+    fn eq(&self, other: &MerkleTree<D>) -> bool {
+        *self == other.hash
+    }
+}
+
+impl<D> fmt::Debug for MerkleTreeChildren<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MerkleTreeChildren::Leaf { data: None } => f.write_str("Leaf"),
+            MerkleTreeChildren::Leaf { data: Some(data) } => {
+                f.debug_struct("Leaf").field("data", data).finish()
+            }
+            MerkleTreeChildren::Branch { left, right } => f
+                .debug_struct("Branch")
+                .field("left", left)
+                .field("right", right)
+                .finish(),
+        }
+    }
+}
+
+impl<D> Clone for MerkleTreeChildren<D> {
+    fn clone(&self) -> Self {
+        match self {
+            MerkleTreeChildren::Leaf { data } => MerkleTreeChildren::Leaf { data: data.clone() },
+            MerkleTreeChildren::Branch { left, right } => MerkleTreeChildren::Branch {
+                left: left.clone(),
+                right: right.clone(),
+            },
+        }
+    }
+}
+
+impl<D> PartialEq for MerkleTreeChildren<D> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MerkleTreeChildren::Leaf { data }, MerkleTreeChildren::Leaf { data: other_data }) => {
+                data == other_data
+            }
+            (
+                MerkleTreeChildren::Branch { left, right },
+                MerkleTreeChildren::Branch {
+                    left: other_left,
+                    right: other_right,
+                },
+            ) => left == other_left && right == other_right,
+            _ => false,
+        }
+    }
+}
+
+impl<D> Eq for MerkleTreeChildren<D> {}
+
+/// Depth-first iterator over leaf hashes, holding only the pending right-side subtrees
+struct Leaves<'a, D> {
+    stack: Vec<&'a MerkleTree<D>>,
+}
+
+impl<'a, D> Iterator for Leaves<'a, D> {
+    type Item = &'a Hash;
+
+    fn next(&mut self) -> Option<&'a Hash> {
+        while let Some(node) = self.stack.pop() {
+            match &node.children {
+                MerkleTreeChildren::Leaf { .. } => return Some(&node.hash),
+                MerkleTreeChildren::Branch { left, right } => {
+                    self.stack.push(right);
+                    self.stack.push(left);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Breadth-first iterator over node hashes, holding the nodes of at most two levels
+struct BreadthFirst<'a, D> {
+    queue: VecDeque<&'a MerkleTree<D>>,
+}
+
+impl<'a, D> Iterator for BreadthFirst<'a, D> {
+    type Item = &'a Hash;
+
+    fn next(&mut self) -> Option<&'a Hash> {
+        let node = self.queue.pop_front()?;
+        if let MerkleTreeChildren::Branch { left, right } = &node.children {
+            self.queue.push_back(left);
+            self.queue.push_back(right);
+        }
+        Some(&node.hash)
+    }
+}
+
+/// Iterator over the levels of an inclusion proof, from the leaf up
+///
+/// Each level is found by walking down from the root again, which trades `O(log n)` steps per
+/// level for not having to store the path.
+struct ProofIter<'a, D> {
+    root: &'a MerkleTree<D>,
+    /// Turns from the root down to the leaf, `true` for right, the last one in bit 0
+    turns: usize,
+    /// Number of levels not yielded yet
+    levels: usize,
+}
+
+impl<'a, D> Iterator for ProofIter<'a, D> {
+    type Item = (bool, &'a Hash);
+
+    fn next(&mut self) -> Option<(bool, &'a Hash)> {
+        if self.levels == 0 {
+            return None;
+        }
+        self.levels -= 1;
+
+        // Remove the lowest turn, then follow the ones above it to its parent
+        let is_right = self.turns & 1 == 1;
+        self.turns >>= 1;
+
+        let mut node = self.root;
+        for level in (0..self.levels).rev() {
+            if let MerkleTreeChildren::Branch { left, right } = &node.children {
+                node = if self.turns >> level & 1 == 1 {
+                    right
+                } else {
+                    left
+                };
+            }
+        }
+
+        match &node.children {
+            MerkleTreeChildren::Branch { left, right } => {
+                Some((is_right, &if is_right { left } else { right }.hash))
+            }
+            MerkleTreeChildren::Leaf { .. } => unreachable!(),
+        }
+    }
+}
+
+/// Carry-propagation state of a tree under construction
+///
+/// Nodes are either whole subtrees or, when only the root is of interest, bare hashes.
+struct Builder<N> {
+    /// Unfinished subtrees that are waiting for corresponding right-side trees
+    /// Entry `i` holds fewer than `arity` perfect subtrees of `arity^i` leaves each, at most
+    /// one for binary trees
+    left_side: Vec<Vec<N>>,
+    /// Combines complete groups of sibling nodes into their parent
+    merge: Merge<N>,
+}
+
+/// How a [`Builder`] combines sibling nodes into their parent
+enum Merge<N> {
+    /// Binary branches over a left and a right node
+    Pair(fn(N, N) -> N),
+    /// Branches over up to the given number of nodes, from left to right
+    Group(usize, fn(Vec<N>) -> N),
+}
+
+impl<N> Merge<N> {
+    /// Number of children of every branch but those on the right edge of the tree
+    fn arity(&self) -> usize {
+        match self {
+            Merge::Pair(_) => 2,
+            Merge::Group(arity, _) => *arity,
+        }
+    }
+
+    /// Parent of the given pending nodes followed by `last`, leaving `pending` empty
+    fn apply(&self, pending: &mut Vec<N>, last: N) -> N {
+        match self {
+            Merge::Pair(merge) => merge(pending.pop().unwrap(), last),
+            Merge::Group(_, merge) => {
+                pending.push(last);
+                merge(mem::take(pending))
+            }
+        }
+    }
+}
+
+impl<N> Clone for Merge<N> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<N> Copy for Merge<N> {}
+
+impl<N> Builder<N> {
+    /// Creates an empty binary builder with room for trees of the given depth
+    fn with_depth(depth: usize, merge: fn(N, N) -> N) -> Builder<N> {
+        Builder {
+            left_side: (0..depth).map(|_| Vec::new()).collect(),
+            merge: Merge::Pair(merge),
+        }
+    }
+
+    /// Creates an empty builder whose branches have up to `arity` children, which must be at
+    /// least 2
+    fn with_arity(arity: usize, merge: fn(Vec<N>) -> N) -> Builder<N> {
+        debug_assert!(arity >= 2);
+        Builder {
+            left_side: Vec::new(),
+            merge: Merge::Group(arity, merge),
+        }
+    }
+
+    /// Adds a single leaf node, merging completed subtrees along the way
+    fn push(&mut self, mut right: N) {
+        let arity = self.merge.arity();
+        // Propagate and merge subtrees
+        for pending in self.left_side.iter_mut() {
+            // Merge with the left-side nodes once they complete a group
+            if pending.len() + 1 < arity {
+                pending.push(right);
+                return;
+            }
+            right = self.merge.apply(pending, right);
+        }
+        self.left_side.push(alloc::vec![right]);
+    }
+
+    /// Folds the remaining subtrees from the bottom up, carrying lone nodes unchanged
+    fn finish(self) -> Option<N> {
+        let mut carried = None;
+        for mut pending in self.left_side {
+            carried = match (pending.pop(), carried) {
+                (None, carried) => carried,
+                (Some(node), None) if pending.is_empty() => Some(node),
+                (Some(node), None) => Some(self.merge.apply(&mut pending, node)),
+                (Some(node), Some(right)) => {
+                    pending.push(node);
+                    Some(self.merge.apply(&mut pending, right))
+                }
+            };
+        }
+        carried
+    }
+}
+
+impl<D: Digest> Builder<MerkleTree<D>> {
+    /// Creates an empty builder of whole trees that grows as leaves are pushed
+    fn new() -> Builder<MerkleTree<D>> {
+        Builder::with_depth(0, MerkleTree::branch)
+    }
+
+    /// Splits a finished tree back into its unfinished subtrees
+    fn from_tree(tree: MerkleTree<D>) -> Builder<MerkleTree<D>> {
+        let count = tree.leaf_count();
+        let mut builder = Builder::with_depth(tree.depth(), MerkleTree::branch);
+
+        // The right edge of the tree holds one perfect subtree per set bit of the leaf count,
+        // the lowest of which is the right-most node itself
+        let lowest = count.trailing_zeros() as usize;
+        let mut node = tree;
+        for level in (lowest + 1..builder.left_side.len()).rev() {
+            if count >> level & 1 == 1 {
+                let MerkleTreeChildren::Branch { left, right } = node.children else {
+                    unreachable!("a tree with multiple pending subtrees must be a branch");
+                };
+                builder.left_side[level].push(*left);
+                node = *right;
+            }
+        }
+        builder.left_side[lowest].push(node);
+
+        builder
+    }
+}
+
+/// Leaf hashes of the given blobs, in order, as the leaves of a tree over them would hold
+/// Feeding them to [`MerkleTree::from_leaf_hashes`] builds the same tree as
+/// [`MerkleTree::construct`]
+pub fn leaf_hashes<D: Digest>(input: &[Data]) -> Vec<Hash> {
+    input.iter().map(hash_data::<D>).collect()
+}
+
+/// Leaf hash of a record of several fields, as the leaves of
+/// [`MerkleTree::construct_records`] hold, for [`Proof::verify_leaf_hash`]
+/// Each field is preceded by its 64-bit little endian length, as
+/// `D(0x00 || len(field_1) || field_1 || ...)`, so moving bytes across a field boundary
+/// changes the hash
+pub fn record_hash<D: Digest>(record: &[Data]) -> Hash {
+    let mut hasher = D::new().chain_update([LEAF_PREFIX]);
+    for field in record {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field);
+    }
+    hasher.finalize().to_vec()
+}
+
+/// Leaf hashes of the given blobs like [`leaf_hashes`], hashed in parallel
+#[cfg(feature = "rayon")]
+pub fn leaf_hashes_parallel<D: Digest>(input: &[Data]) -> Vec<Hash> {
+    use rayon::prelude::*;
+
+    input.par_iter().map(hash_data::<D>).collect()
+}
+
+/// Merges two sibling hashes into their parent's, for builders that only track hashes
+fn merge_hashes<D: Digest>(left: Hash, right: Hash) -> Hash {
+    hash_concat::<D>(&left, &right)
+}
+
+/// Checks that a tree can be built over the given number of leaves
+fn check_leaf_count(len: usize) -> Result<(), MerkleError> {
+    match len {
+        0 => Err(MerkleError::EmptyInput),
+        len if len > MAX_LEAVES => Err(MerkleError::TooLarge { len }),
+        _ => Ok(()),
+    }
+}
+
+/// Leaf count of the left subtree of a branch with `count` leaves
+/// This is the largest power of two strictly smaller than `count`, which must be at least 2
+fn split_point(count: usize) -> usize {
+    1 << (usize::BITS - 1 - (count - 1).leading_zeros())
+}
+
+/// Hashes of empty subtrees of every height from 0 up to `height` inclusive, each computed
+/// once from the one below
+fn empty_hashes<D: Digest>(height: usize) -> Vec<Hash> {
+    let mut empty = Vec::with_capacity(height + 1);
+    empty.push(MerkleTree::<D>::empty_leaf_hash());
+    for level in 0..height {
+        empty.push(hash_concat::<D>(&empty[level], &empty[level]));
+    }
+    empty
+}
+
+/// Compares two hashes in time that does not depend on where they differ
+/// Only their lengths, which are not secret, can end the comparison early
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    let diff = a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y));
+    a.len() == b.len() && core::hint::black_box(diff) == 0
+}
+
+/// Domain separation prefix of leaf hashes
+const LEAF_PREFIX: u8 = 0x00;
+/// Domain separation prefix of internal node hashes
+const NODE_PREFIX: u8 = 0x01;
+
+fn hash_data<D: Digest>(data: impl AsRef<[u8]>) -> Hash {
+    D::new()
+        .chain_update([LEAF_PREFIX])
+        .chain_update(data.as_ref())
+        .finalize()
+        .to_vec()
+}
+
+/// Hashes a leaf salted with its index, as `D(0x00 || index as 64-bit little endian || data)`
+fn hash_indexed<D: Digest>(index: usize, data: &Data) -> Hash {
+    D::new()
+        .chain_update([LEAF_PREFIX])
+        .chain_update((index as u64).to_le_bytes())
+        .chain_update(data)
+        .finalize()
+        .to_vec()
+}
+
+/// Hashes two child hashes into their parent's
+/// Both halves are fed to the hasher in place, so only the resulting hash is allocated
+fn hash_concat<D: Digest>(h1: &Hash, h2: &Hash) -> Hash {
+    hash_children::<D>(&[h1, h2])
+}
+
+/// Hashes two child hashes into their parent's like [`hash_concat`], with the separator byte,
+/// if any, between them, i.e. `D(0x01 || h1 || sep || h2)`
+fn hash_concat_sep<D: Digest>(h1: &Hash, h2: &Hash, sep: Option<u8>) -> Hash {
+    let mut hasher = D::new().chain_update([NODE_PREFIX]).chain_update(h1);
+    if let Some(sep) = sep {
+        hasher.update([sep]);
+    }
+    hasher.chain_update(h2).finalize().to_vec()
+}
+
+/// Hashes two child hashes into their parent's, each preceded by its length as a 64-bit
+/// little endian integer, i.e. `D(0x01 || len(h1) || h1 || len(h2) || h2)`
+/// Unlike with [`hash_concat`], children of varying lengths cannot be regrouped into the
+/// same parent hash
+fn hash_concat_prefixed<D: Digest>(h1: &Hash, h2: &Hash) -> Hash {
+    D::new()
+        .chain_update([NODE_PREFIX])
+        .chain_update((h1.len() as u64).to_le_bytes())
+        .chain_update(h1)
+        .chain_update((h2.len() as u64).to_le_bytes())
+        .chain_update(h2)
+        .finalize()
+        .to_vec()
+}
+
+/// Hashes the concatenation of any number of child hashes into their parent's, which is what
+/// [`hash_concat`] does for two of them
+fn hash_children<D: Digest>(children: &[&Hash]) -> Hash {
+    children
+        .iter()
+        .fold(D::new().chain_update([NODE_PREFIX]), |hasher, child| {
+            hasher.chain_update(child)
+        })
+        .finalize()
+        .to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256, Sha512};
+
+    use super::{
+        check_leaf_count, ct_eq, hash_children, hash_concat, hash_concat_prefixed, hash_concat_sep,
+        hash_data, leaf_hashes, record_hash, to_hex, EmptyHashes, Event, MerkleError, MerkleTree,
+        MerkleTreeChildren, ProofError, Sha256Tree, SparseMerkleTree, HASH_LEN, MAX_LEAVES,
+    };
+
+    #[test]
+    fn manual_hash_calculation() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        let a = hash_data::<Sha256>(&input[0]);
+        let b = hash_data::<Sha256>(&input[1]);
+        let c = hash_data::<Sha256>(&input[2]);
+        let d = hash_data::<Sha256>(&input[3]);
+
+        let ab = hash_concat::<Sha256>(&a, &b);
+        let cd = hash_concat::<Sha256>(&c, &d);
+
+        let abcd = hash_concat::<Sha256>(&ab, &cd);
+
+        assert_eq!(abcd, mt.hash);
+        assert_eq!(&abcd, mt.root());
+
+        assert!(Sha256Tree::verify(&input, &mt.hash));
+    }
+
+    #[test]
+    fn different_sizes() {
+        for size in 1..10 {
+            let input: Vec<_> = (0..(1 << size)).map(|i| vec![i as u8]).collect();
+
+            let mt = Sha256Tree::construct(&input);
+            assert!(Sha256Tree::verify(&input, &mt.hash));
+        }
+    }
+
+    #[test]
+    fn non_power_of_two_sizes() {
+        for size in [1, 3, 5, 6, 7] {
+            let input: Vec<_> = (0..size).map(|i| vec![i]).collect();
+
+            let mt = Sha256Tree::construct(&input);
+            assert!(Sha256Tree::verify(&input, &mt.hash));
+        }
+    }
+
+    #[test]
+    fn odd_node_is_carried_unchanged() {
+        let input: Vec<_> = (0..3).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        let ab = hash_concat::<Sha256>(
+            &hash_data::<Sha256>(&input[0]),
+            &hash_data::<Sha256>(&input[1]),
+        );
+        let c = hash_data::<Sha256>(&input[2]);
+
+        assert_eq!(hash_concat::<Sha256>(&ab, &c), mt.hash);
+    }
+
+    #[test]
+    fn single_leaf_is_root() {
+        let input = vec![vec![42]];
+
+        let mt = Sha256Tree::construct(&input);
+        assert_eq!(hash_data::<Sha256>(&input[0]), mt.hash);
+    }
+
+    #[test]
+    fn single_leaf_proof() {
+        let input = vec![vec![42]];
+
+        let mt = Sha256Tree::construct(&input);
+        assert_eq!(mt.leaf_count(), 1);
+        assert_eq!(mt.depth(), 1);
+        assert!(mt.left().is_none());
+
+        // The leaf is the root, so its proof is empty
+        let proof = mt.prove(0).unwrap();
+        assert!(proof.siblings().is_empty());
+        assert!(proof.directions().is_empty());
+        assert_eq!(proof.compute_root(&input[0]).unwrap(), mt.hash);
+        assert!(proof.verify(&input[0], &mt.hash));
+        assert!(proof.verify_at(&input[0], 0, 1, &mt.hash));
+        assert!(!proof.verify(&vec![43], &mt.hash));
+        assert!(!proof.verify_at(&input[0], 1, 1, &mt.hash));
+        assert!(!proof.verify_at(&input[0], 0, 2, &mt.hash));
+
+        assert_eq!(
+            mt.try_prove(1).err(),
+            Some(ProofError::IndexOutOfRange { index: 1, len: 1 })
+        );
+        assert_eq!(proof.to_bytes(), [0]);
+    }
+
+    #[test]
+    fn proof_generation() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        let proof = mt.prove(3).unwrap();
+
+        // One sibling per level below the root
+        assert_eq!(proof.siblings.len(), 3);
+        assert_eq!(proof.directions(), [true, true, false]);
+        assert_eq!(proof.siblings[0], hash_data::<Sha256>(&input[2]));
+
+        assert!(mt.prove(8).is_none());
+    }
+
+    #[test]
+    fn try_prove_errors() {
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        assert_eq!(mt.try_prove(3), Ok(mt.prove(3).unwrap()));
+        assert_eq!(
+            mt.try_prove(5),
+            Err(ProofError::IndexOutOfRange { index: 5, len: 5 })
+        );
+        assert_eq!(mt.prove(5), None);
+    }
+
+    #[test]
+    fn different_digests() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+
+        let sha256 = MerkleTree::<Sha256>::construct(&input);
+        let sha512 = MerkleTree::<Sha512>::construct(&input);
+
+        assert_ne!(sha256.hash, sha512.hash);
+        assert!(MerkleTree::<Sha512>::verify(&input, &sha512.hash));
+    }
+
+    #[test]
+    fn second_preimage_resistance() {
+        let input: Vec<_> = (0..2).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        // Present the concatenated child hashes as if they were a single leaf
+        let a = hash_data::<Sha256>(&input[0]);
+        let b = hash_data::<Sha256>(&input[1]);
+        let forged = vec![[a, b].concat()];
+
+        assert!(!Sha256Tree::verify(&forged, &mt.hash));
+    }
+
+    #[test]
+    fn root_hex() {
+        let input = vec![vec![]];
+
+        let mt = Sha256Tree::construct(&input);
+        assert_eq!(
+            mt.root_hex(),
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d"
+        );
+    }
+
+    #[test]
+    fn try_construct_errors() {
+        assert_eq!(
+            Sha256Tree::try_construct(&[]).err(),
+            Some(MerkleError::EmptyInput)
+        );
+
+        let input: Vec<_> = (0..3).map(|i| vec![i]).collect();
+        assert!(Sha256Tree::try_construct(&input).is_ok());
+    }
+
+    #[test]
+    fn push_matches_construct() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mut mt = Sha256Tree::construct(&input[..1]);
+        for size in 2..=input.len() {
+            mt.push(input[size - 1].clone()).unwrap();
+            assert_eq!(mt.hash, Sha256Tree::construct(&input[..size]).hash);
+        }
+    }
+
+    #[test]
+    fn extend_matches_construct() {
+        let input: Vec<_> = (0..16).map(|i| vec![i]).collect();
+
+        let mut mt = Sha256Tree::construct(&input[..8]);
+        mt.extend(&input[8..]).unwrap();
+        assert_eq!(mt, Sha256Tree::construct(&input));
+
+        let mut mt = Sha256Tree::construct(&input[..5]);
+        mt.extend(&[]).unwrap();
+        assert_eq!(mt, Sha256Tree::construct(&input[..5]));
+        mt.extend(&input[5..11]).unwrap();
+        assert_eq!(mt, Sha256Tree::construct(&input[..11]));
+    }
+
+    #[test]
+    fn from_iterator() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt: Sha256Tree = (0..8).map(|i| vec![i]).collect();
+        assert_eq!(mt.hash, Sha256Tree::construct(&input).hash);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_construction() {
+        for size in [1, 5, 1024] {
+            let input: Vec<_> = (0..size).map(|i: u32| i.to_le_bytes().to_vec()).collect();
+
+            let sequential = Sha256Tree::construct(&input);
+            let parallel = Sha256Tree::construct_parallel(&input);
+            assert_eq!(sequential.hash, parallel.hash);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        let json = serde_json::to_string(&mt).unwrap();
+        assert!(json.contains(&mt.root_hex()));
+
+        let restored: Sha256Tree = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.hash, mt.hash);
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+        assert!(restored.prove(5).unwrap().verify(&input[5], &mt.hash));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn proven_leaf_round_trip() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let mut bundle = mt.prove_data(5).unwrap();
+        assert_eq!(bundle.index, 5);
+        assert_eq!(bundle.data, None);
+        assert!(!bundle.verify(&mt.hash));
+        assert!(mt.prove_data(8).is_none());
+
+        bundle.data = Some(input[5].clone());
+        let json = serde_json::to_string(&bundle).unwrap();
+        assert!(json.contains(&bundle.proof.to_hex()));
+
+        let restored: super::ProvenLeaf = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, bundle);
+        assert!(restored.verify(&mt.hash));
+    }
+
+    #[test]
+    fn clone_and_equality() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        assert_eq!(mt.clone(), mt);
+
+        let other: Vec<_> = (1..9).map(|i| vec![i]).collect();
+        assert_ne!(Sha256Tree::construct(&other), mt);
+    }
+
+    #[test]
+    fn leaf_count_and_depth() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        assert_eq!(mt.leaf_count(), 8);
+        assert_eq!(mt.depth(), 4);
+
+        let mt = Sha256Tree::construct(&input[..5]);
+        assert_eq!(mt.leaf_count(), 5);
+        assert_eq!(mt.depth(), 4);
+    }
+
+    #[test]
+    fn node_counts() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        assert_eq!(mt.leaf_count(), 8);
+        assert_eq!(mt.internal_count(), 7);
+        assert_eq!(mt.node_count(), 15);
+
+        let mt = Sha256Tree::construct(&input[..5]);
+        assert_eq!(mt.internal_count(), 4);
+        assert_eq!(mt.node_count(), 9);
+    }
+
+    #[test]
+    fn update_leaf() {
+        let mut input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mut mt = Sha256Tree::construct(&input);
+        mt.update(2, vec![42]).unwrap();
+
+        input[2] = vec![42];
+        assert_eq!(mt, Sha256Tree::construct(&input));
+
+        assert_eq!(
+            mt.update(8, vec![42]),
+            Err(MerkleError::IndexOutOfRange { index: 8, len: 8 })
+        );
+    }
+
+    #[test]
+    fn truncate() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        assert_eq!(mt.truncate(4), Sha256Tree::construct(&input[..4]));
+        for len in 1..=8 {
+            assert_eq!(mt.truncate(len), Sha256Tree::construct(&input[..len]));
+        }
+    }
+
+    #[test]
+    fn leaf_iteration() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        let expected: Vec<_> = input.iter().map(hash_data::<Sha256>).collect();
+        assert!(mt.leaves().eq(&expected));
+    }
+
+    #[test]
+    fn enumerate_leaves() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let leaves: Vec<_> = mt.enumerate_leaves().collect();
+        let indices: Vec<_> = leaves.iter().map(|&(index, _)| index).collect();
+        assert_eq!(indices, (0..8).collect::<Vec<_>>());
+
+        for (index, hash) in leaves {
+            assert_eq!(*hash, hash_data::<Sha256>(&input[index]));
+        }
+    }
+
+    #[test]
+    fn leaf_lookup() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        assert_eq!(mt.index_of(&vec![5]), Some(5));
+        assert_eq!(mt.index_of(&vec![42]), None);
+    }
+
+    #[test]
+    fn contains() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        assert!(mt.contains(&vec![5]));
+        assert!(!mt.contains(&vec![8]));
+    }
+
+    #[test]
+    fn from_leaf_hashes() {
+        let input: Vec<_> = (0..6).map(|i| vec![i]).collect();
+
+        let hashes: Vec<_> = input.iter().map(hash_data::<Sha256>).collect();
+
+        let mt = Sha256Tree::from_leaf_hashes(&hashes);
+        assert_eq!(mt, Sha256Tree::construct(&input));
+    }
+
+    #[test]
+    fn diff_trees() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mut other = input.clone();
+        other[1][0] += 10;
+        other[5][0] += 10;
+
+        let mt = Sha256Tree::construct(&input);
+        let other_mt = Sha256Tree::construct(&other);
+
+        assert_eq!(mt.diff(&other_mt), [1, 5]);
+        assert!(mt.diff(&mt).is_empty());
+        assert_eq!(mt.diff(&Sha256Tree::construct(&input[..3])).len(), 8);
+    }
+
+    #[test]
+    fn first_diff_subtree() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mut other = input.clone();
+        other[4][0] += 10;
+        other[7][0] += 10;
+
+        let mt = Sha256Tree::construct(&input);
+        let other_mt = Sha256Tree::construct(&other);
+
+        let (path, node) = mt.first_diff_subtree(&other_mt).unwrap();
+        assert_eq!(path, [true]);
+        assert_eq!(node, mt.node(&[true]).unwrap());
+
+        // A single differing leaf is found all the way down
+        other[7][0] -= 10;
+        let other_mt = Sha256Tree::construct(&other);
+        let (path, node) = mt.first_diff_subtree(&other_mt).unwrap();
+        assert_eq!(path, [true, false, false]);
+        assert_eq!(node.hash, hash_data::<Sha256>(&input[4]));
+
+        assert!(mt.first_diff_subtree(&mt).is_none());
+    }
+
+    #[test]
+    fn root_base64() {
+        let input = vec![vec![]];
+
+        let mt = Sha256Tree::construct(&input);
+        assert_eq!(
+            mt.root_base64(),
+            "bjQLnP+zepicpUTmu3gKLHiQHT+zNzh2hRGjBhevoB0="
+        );
+    }
+
+    #[test]
+    fn leaf_count_limit() {
+        assert_eq!(check_leaf_count(MAX_LEAVES), Ok(()));
+        assert_eq!(
+            check_leaf_count(MAX_LEAVES + 1),
+            Err(MerkleError::TooLarge {
+                len: MAX_LEAVES + 1
+            })
+        );
+
+        // The node count of a maximal tree is exactly the largest `usize`
+        assert_eq!(MAX_LEAVES + (MAX_LEAVES - 1), usize::MAX);
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn leaf_count_limit_64_bit() {
+        assert_eq!(MAX_LEAVES, 1 << 63);
+        assert!(check_leaf_count(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn streaming_verification() {
+        let input: Vec<_> = (0..=255).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        assert!(Sha256Tree::verify_root(&input, &mt.hash));
+        assert!(Sha256Tree::verify_root(
+            &input[..255],
+            &Sha256Tree::construct(&input[..255]).hash
+        ));
+
+        let mut other = input.clone();
+        other[100][0] += 1;
+        assert_eq!(
+            Sha256Tree::verify_root(&other, &mt.hash),
+            Sha256Tree::verify(&other, &mt.hash)
+        );
+        assert!(!Sha256Tree::verify_root(&other, &mt.hash));
+        assert!(!Sha256Tree::verify_root(&[], &mt.hash));
+    }
+
+    #[test]
+    fn node_navigation() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        assert_eq!(mt.node_hash(&[]), Some(&mt.hash));
+        assert_eq!(
+            mt.node_hash(&[false, false, false]),
+            Some(&hash_data::<Sha256>(&input[0]))
+        );
+        assert_eq!(
+            mt.node_hash(&[true, false, true]),
+            Some(&hash_data::<Sha256>(&input[5]))
+        );
+        assert_eq!(mt.node_hash(&[false, false, false, false]), None);
+    }
+
+    #[test]
+    fn variadic_node_hash() {
+        let a = hash_data::<Sha256>(&vec![0]);
+        let b = hash_data::<Sha256>(&vec![1]);
+
+        assert_eq!(
+            hash_children::<Sha256>(&[&a, &b]),
+            hash_concat::<Sha256>(&a, &b)
+        );
+    }
+
+    #[test]
+    fn borrowed_slices() {
+        assert_eq!(
+            Sha256Tree::construct_from_slices(&[b"a", b"b"]),
+            Sha256Tree::construct(&[b"a".to_vec(), b"b".to_vec()])
+        );
+        assert_eq!(
+            Sha256Tree::construct_from_slices(&["a", "b", "c"]),
+            Sha256Tree::construct(&[b"a".to_vec(), b"b".to_vec(), b"c".to_vec()])
+        );
+    }
+
+    #[test]
+    fn construct_with_proofs() {
+        for size in [4, 7] {
+            let input: Vec<_> = (0..size).map(|i| vec![i]).collect();
+
+            let (mt, proofs) = Sha256Tree::construct_with_proofs(&input);
+            assert_eq!(proofs.len(), input.len());
+
+            for (i, proof) in proofs.iter().enumerate() {
+                assert!(proof.verify(&input[i], &mt.hash));
+                assert_eq!(*proof, mt.prove(i).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn display_tree() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let rendered = mt.to_string();
+        let lines: Vec<_> = rendered.lines().collect();
+
+        // 4 leaves, 2 inner branches and the root
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], format!("branch {}", &mt.root_hex()[..8]));
+        assert_eq!(lines.iter().filter(|line| line.contains("leaf")).count(), 4);
+        assert!(lines[1].starts_with("  branch "));
+        assert!(lines[2].starts_with("    leaf "));
+    }
+
+    #[test]
+    fn path_to_leaf() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let path = mt.path_to_leaf(0).unwrap();
+        assert_eq!(path.len(), 4);
+        assert_eq!(path[0], mt.hash);
+        assert_eq!(path[3], hash_data::<Sha256>(&input[0]));
+
+        // The carried-up leaf of an odd tree sits just below the root
+        let mt = Sha256Tree::construct(&input[..5]);
+        assert_eq!(
+            mt.path_to_leaf(4).unwrap(),
+            vec![mt.hash.clone(), hash_data::<Sha256>(&input[4])]
+        );
+
+        assert_eq!(mt.path_to_leaf(5), None);
+    }
+
+    /// Checks that a tree whose leaf hashes cannot be reproduced refuses to hash new leaf
+    /// data and stays valid when a leaf is removed
+    fn assert_mutations_refused(mut mt: Sha256Tree) {
+        let before = mt.clone();
+        assert_eq!(mt.update(0, vec![9]), Err(MerkleError::UnsupportedScheme));
+        assert_eq!(mt.push(vec![9]), Err(MerkleError::UnsupportedScheme));
+        assert_eq!(mt.extend(&[vec![9]]), Err(MerkleError::UnsupportedScheme));
+        assert_eq!(
+            mt.append_proved(vec![9]).err(),
+            Some(MerkleError::UnsupportedScheme)
+        );
+        assert_eq!(mt, before);
+
+        mt.remove(1).unwrap();
+        assert!(mt.validate());
+        assert!(mt
+            .prove(0)
+            .unwrap()
+            .verify_leaf_hash(&before.level_hashes(2)[0], mt.root()));
+        assert_eq!(
+            Sha256Tree::join(mt, Sha256Tree::construct(&vec![vec![0]; 4])),
+            Err(MerkleError::UnsupportedScheme)
+        );
+    }
+
+    #[test]
+    fn keyed_mutations() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+        assert_mutations_refused(Sha256Tree::construct_keyed(&input, b"key"));
+    }
+
+    #[test]
+    fn custom_leaf_mutations() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+        assert_mutations_refused(Sha256Tree::construct_with(&input, |data| {
+            Sha256::digest(data).to_vec()
+        }));
+    }
+
+    #[test]
+    fn record_mutations() {
+        let records: Vec<_> = (0..4).map(|i| vec![vec![i], vec![i + 1]]).collect();
+        assert_mutations_refused(Sha256Tree::construct_records(&records));
+    }
+
+    #[test]
+    fn indexed_mutations() {
+        let mut input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mut mt = Sha256Tree::construct_indexed(&input);
+
+        input[2] = vec![9];
+        mt.update(2, vec![9]).unwrap();
+        assert_eq!(mt, Sha256Tree::construct_indexed(&input));
+
+        input.extend([vec![5], vec![6]]);
+        mt.push(vec![5]).unwrap();
+        mt.extend(&[vec![6]]).unwrap();
+        assert_eq!(mt, Sha256Tree::construct_indexed(&input));
+        assert_eq!(mt.truncate(3), Sha256Tree::construct_indexed(&input[..3]));
+        assert!(mt.validate());
+    }
+
+    #[test]
+    fn prefixed_mutations() {
+        let mut input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mut mt = Sha256Tree::construct_prefixed(&input);
+        assert!(mt.validate());
+
+        input[2] = vec![9];
+        mt.update(2, vec![9]).unwrap();
+        assert_eq!(mt, Sha256Tree::construct_prefixed(&input));
+
+        input.push(vec![5]);
+        let (root, proof) = mt.append_proved(vec![5]).unwrap();
+        assert_eq!(mt, Sha256Tree::construct_prefixed(&input));
+        assert!(proof.verify_prefixed(&input[5], &root));
+        assert_eq!(mt.truncate(3), Sha256Tree::construct_prefixed(&input[..3]));
+
+        mt.remove(1).unwrap();
+        assert!(mt.validate());
+        assert!(mt.prove(0).unwrap().verify_prefixed(&input[0], mt.root()));
+    }
+
+    #[test]
+    fn separated_mutations() {
+        let mut input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mut mt = Sha256Tree::construct_with_separator(&input, 0xff);
+
+        input[4] = vec![9];
+        mt.update(4, vec![9]).unwrap();
+        assert_eq!(mt, Sha256Tree::construct_with_separator(&input, 0xff));
+
+        input.extend([vec![5], vec![6], vec![7]]);
+        mt.extend(&input[5..]).unwrap();
+        assert_eq!(mt, Sha256Tree::construct_with_separator(&input, 0xff));
+        assert!(mt.validate());
+
+        mt.remove(7).unwrap();
+        assert!(mt.validate());
+        assert!(mt
+            .prove(3)
+            .unwrap()
+            .verify_with_separator(&input[3], mt.root(), 0xff));
+        assert_eq!(
+            Sha256Tree::join(mt, Sha256Tree::construct_with_separator(&input, 0xfe)),
+            Err(MerkleError::UnsupportedScheme)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "EmptyInput")]
+    fn separator_empty() {
+        Sha256Tree::construct_with_separator(&[], 0xff);
+    }
+
+    #[test]
+    #[should_panic(expected = "EmptyInput")]
+    fn instrumented_empty() {
+        Sha256Tree::construct_instrumented(&[], |_| {});
+    }
+
+    #[test]
+    fn scheme_equality() {
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+
+        // Equal hashes, but only one of the trees can hash new leaves
+        let standard = Sha256Tree::construct(&input);
+        let opaque = Sha256Tree::construct_with(&input, |data| hash_data::<Sha256>(data));
+        assert_eq!(standard.root(), opaque.root());
+        assert_ne!(standard, opaque);
+        assert_eq!(standard.clone(), standard);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    #[should_panic(expected = "EmptyInput")]
+    fn parallel_empty() {
+        Sha256Tree::construct_parallel(&[]);
+    }
+
+    #[test]
+    fn empty_root() {
+        assert_eq!(Sha256Tree::empty_root(), Sha256Tree::empty_root());
+        assert_eq!(
+            to_hex(&Sha256Tree::empty_root()),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        assert_eq!(Sha256Tree::root_or_empty(&[]), Sha256Tree::empty_root());
+
+        let input = vec![vec![0]];
+        assert_eq!(
+            Sha256Tree::root_or_empty(&input),
+            Sha256Tree::construct(&input).hash
+        );
+    }
+
+    #[test]
+    fn join_trees() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let left = Sha256Tree::construct(&input[..4]);
+        let right = Sha256Tree::construct(&input[4..]);
+        let joined = Sha256Tree::join(left.clone(), right).unwrap();
+        assert_eq!(joined, Sha256Tree::construct(&input));
+
+        // A shallower right side must instead be built into the whole tree
+        let short = Sha256Tree::construct(&input[4..6]);
+        assert_eq!(
+            Sha256Tree::join(left.clone(), short),
+            Err(MerkleError::DepthMismatch { left: 3, right: 2 })
+        );
+
+        // Appending after an imperfect left side would break the tree shape
+        let uneven = Sha256Tree::construct(&input[..3]);
+        assert_eq!(
+            Sha256Tree::join(uneven, left),
+            Err(MerkleError::NotPowerOfTwo(3))
+        );
+    }
+
+    #[test]
+    fn memory_size() {
+        let sizes: Vec<_> = [2, 4, 8]
+            .into_iter()
+            .map(|len| {
+                let input: Vec<_> = (0..len).map(|i| vec![i]).collect();
+                Sha256Tree::construct(&input).memory_size()
+            })
+            .collect();
+
+        // Trees of n leaves have 2n - 1 nodes of the same size each
+        let node = sizes[0] / 3;
+        assert_eq!(sizes, vec![3 * node, 7 * node, 15 * node]);
+        assert!(node >= 32);
+    }
+
+    #[test]
+    fn constant_time_comparison() {
+        assert!(ct_eq(&[1, 2, 3], &[1, 2, 3]));
+        assert!(ct_eq(&[], &[]));
+        assert!(!ct_eq(&[1, 2, 3], &[1, 2, 4]));
+        assert!(!ct_eq(&[0, 2, 3], &[1, 2, 3]));
+        assert!(!ct_eq(&[1, 2], &[1, 2, 3]));
+
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+        let mut wrong = mt.hash.clone();
+        wrong[31] ^= 1;
+
+        assert!(Sha256Tree::verify(&input, &mt.hash));
+        assert!(!Sha256Tree::verify(&input, &wrong));
+        assert!(mt.prove(4).unwrap().verify(&input[4], &mt.hash));
+        assert!(!mt.prove(4).unwrap().verify(&input[4], &wrong));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn construct_from_reader() {
+        use std::io::{Cursor, ErrorKind};
+
+        let bytes: Vec<u8> = (0..1024).map(|i| i as u8).collect();
+        let chunks: Vec<_> = bytes.chunks(256).map(|chunk| chunk.to_vec()).collect();
+
+        let mt = Sha256Tree::construct_from_reader(Cursor::new(&bytes), 256).unwrap();
+        assert_eq!(mt, Sha256Tree::construct(&chunks));
+
+        // The trailing partial block becomes a leaf of its own
+        let mt = Sha256Tree::construct_from_reader(Cursor::new(&bytes[..1000]), 256).unwrap();
+        assert_eq!(mt.leaf_count(), 4);
+        assert!(mt
+            .prove(3)
+            .unwrap()
+            .verify(&bytes[768..1000].to_vec(), &mt.hash));
+
+        let empty = Sha256Tree::construct_from_reader(Cursor::new(&[]), 256);
+        assert_eq!(empty.unwrap_err().kind(), ErrorKind::InvalidInput);
+        let unchunked = Sha256Tree::construct_from_reader(Cursor::new(&bytes), 0);
+        assert_eq!(unchunked.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn custom_leaf_hashing() {
+        let input: Vec<_> = (0..4).map(|i| vec![i; i as usize]).collect();
+
+        let length_prefixed = |data: &Vec<u8>| {
+            let mut prefixed = (data.len() as u64).to_be_bytes().to_vec();
+            prefixed.extend_from_slice(data);
+            hash_data::<Sha256>(prefixed)
+        };
+        let mt = Sha256Tree::construct_with(&input, length_prefixed);
+
+        assert_ne!(mt.hash, Sha256Tree::construct(&input).hash);
+        assert_eq!(mt.leaves().next().unwrap(), &length_prefixed(&input[0]));
+
+        // With the default rule plugged in, the hashes do not change
+        let default = Sha256Tree::construct_with(&input, |data| hash_data::<Sha256>(data));
+        assert_eq!(default.root(), Sha256Tree::construct(&input).root());
+    }
+
+    #[test]
+    fn validate() {
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+        let mut mt = Sha256Tree::construct(&input);
+        assert!(mt.validate());
+
+        // A corrupted inner branch no longer matches its children, nor its parent
+        if let MerkleTreeChildren::Branch { right, .. } = &mut mt.children {
+            right.hash[0] ^= 1;
+        }
+        assert!(!mt.validate());
+
+        // Leaves are trusted, but their parent is checked against them
+        let mut mt = Sha256Tree::construct(&input);
+        if let MerkleTreeChildren::Branch { left, .. } = &mut mt.children {
+            left.hash = Sha256Tree::construct(&input[..3]).hash;
+        }
+        assert!(!mt.validate());
+    }
+
+    #[test]
+    fn hash_set_of_trees() {
+        use std::collections::HashSet;
+
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+
+        let mut set = HashSet::new();
+        set.insert(Sha256Tree::construct(&input));
+        set.insert(Sha256Tree::construct(&input));
+        assert_eq!(set.len(), 1);
+
+        set.insert(Sha256Tree::construct(&input[..3]));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn compare_with_root() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        assert!(mt == mt.root().clone());
+        assert!(mt.root().clone() == mt);
+        assert!(mt != hash_data::<Sha256>(&input[0]));
+        assert!(hash_data::<Sha256>(&input[0]) != mt);
+    }
+
+    #[test]
+    fn keyed_construction() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+
+        let first = Sha256Tree::construct_keyed(&input, b"first key");
+        let second = Sha256Tree::construct_keyed(&input, b"second key");
+
+        assert_ne!(first.hash, second.hash);
+        assert_ne!(first.hash, Sha256Tree::construct(&input).hash);
+        assert_eq!(first, Sha256Tree::construct_keyed(&input, b"first key"));
+    }
+
+    #[test]
+    fn hash_length() {
+        assert_eq!(HASH_LEN, 32);
+        assert_eq!(Sha256Tree::hash_len(), HASH_LEN);
+        assert_eq!(hash_data::<Sha256>([1, 2, 3]).len(), HASH_LEN);
+
+        assert_eq!(MerkleTree::<Sha512>::hash_len(), 64);
+    }
+
+    #[test]
+    fn extract_subtree() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let left = mt.subtree(&[false]).unwrap();
+        assert_eq!(left.hash, Sha256Tree::construct(&input[..4]).hash);
+        assert_eq!(left, Sha256Tree::construct(&input[..4]));
+
+        assert_eq!(mt.subtree(&[]).unwrap(), mt);
+        assert!(mt.subtree(&[true, true, true, false]).is_none());
+    }
+
+    #[test]
+    fn empty_leaf_sentinel() {
+        let sentinel = Sha256Tree::empty_leaf_hash();
+        assert_ne!(sentinel, hash_data::<Sha256>(vec![]));
+        assert_eq!(
+            Some(&sentinel),
+            EmptyHashes::<Sha256>::new(0).subtree_hash(0)
+        );
+
+        // An absent key of a sparse tree is proven against the sentinel leaf
+        let mut smt = SparseMerkleTree::<Sha256>::new();
+        smt.insert(b"present", vec![]);
+        let proof = smt.prove(b"absent");
+        assert!(proof.verify(b"absent", None, &smt.root()));
+        assert!(!proof.verify(b"absent", Some(&vec![]), &smt.root()));
+
+        // Padded trees fill the positions after the input with the sentinel
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct_padded_empty(&input);
+        let mut hashes = leaf_hashes::<Sha256>(&input);
+        hashes.resize(8, sentinel.clone());
+        assert_eq!(mt, Sha256Tree::from_leaf_hashes(&hashes));
+        assert_eq!(mt.leaf_count(), 8);
+        assert_eq!(mt.prove(4).unwrap().siblings()[0], sentinel);
+        assert_ne!(mt, Sha256Tree::construct_padded(&input, vec![]));
+
+        let powers = [1, 2, 4].map(|len| input[..len].to_vec());
+        for input in powers {
+            assert_eq!(
+                Sha256Tree::construct_padded_empty(&input),
+                Sha256Tree::construct(&input)
+            );
+        }
+    }
+
+    #[test]
+    fn hash_concat_without_concatenation() {
+        let h1 = hash_data::<Sha256>([1]);
+        let h2 = hash_data::<Sha256>([2]);
+
+        // Hashing the halves in place must match hashing an explicit concatenation
+        let concatenated: Vec<u8> = [0x01].iter().chain(&h1).chain(&h2).copied().collect();
+        assert_eq!(
+            hash_concat::<Sha256>(&h1, &h2),
+            Sha256::digest(concatenated).to_vec()
+        );
+    }
+
+    #[test]
+    fn lazy_proof() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let levels: Vec<_> = mt.prove_iter(3).unwrap().collect();
+        let proof = mt.prove(3).unwrap();
+        assert_eq!(levels.len(), 3);
+        for ((is_right, sibling), (direction, expected)) in levels
+            .into_iter()
+            .zip(proof.directions().into_iter().zip(proof.siblings()))
+        {
+            assert_eq!(is_right, direction);
+            assert_eq!(sibling, expected);
+        }
+
+        assert!(mt.prove_iter(8).is_none());
+    }
+
+    #[test]
+    fn rfc6962_vector() {
+        // Leaves and root of the five-leaf tree in the Certificate Transparency test data
+        let input = vec![
+            vec![],
+            vec![0x00],
+            vec![0x10],
+            vec![0x20, 0x21],
+            vec![0x30, 0x31],
+        ];
+
+        let mt = Sha256Tree::construct_rfc6962(&input);
+        assert_eq!(
+            mt.root_hex(),
+            "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4"
+        );
+        assert_eq!(mt, Sha256Tree::construct(&input));
+    }
+
+    #[test]
+    fn children() {
+        let input = vec![vec![0], vec![1]];
+        let mt = Sha256Tree::construct(&input);
+
+        match mt.children() {
+            MerkleTreeChildren::Branch { left, right } => {
+                assert_eq!(*left.children(), MerkleTreeChildren::Leaf { data: None });
+                assert_eq!(*right.root(), hash_data::<Sha256>(&input[1]));
+            }
+            MerkleTreeChildren::Leaf { .. } => panic!("a two-leaf tree must be a branch"),
+        }
+    }
+
+    #[test]
+    fn indexed_construction() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+        let mut swapped = input.clone();
+        swapped.swap(1, 2);
+
+        let mt = Sha256Tree::construct_indexed(&input);
+        assert_ne!(mt.hash, Sha256Tree::construct_indexed(&swapped).hash);
+        assert_ne!(mt.hash, Sha256Tree::construct(&input).hash);
+
+        let mut salted = 2u64.to_le_bytes().to_vec();
+        salted.extend_from_slice(&input[2]);
+        assert_eq!(mt.leaves().nth(2).unwrap(), &hash_data::<Sha256>(salted));
+    }
+
+    #[test]
+    fn verify_proof_against_tree() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+        let other = Sha256Tree::construct(&input[1..]);
+
+        let proof = mt.prove(3).unwrap();
+        assert!(mt.verify_proof(&input[3], &proof));
+        assert!(!mt.verify_proof(&input[4], &proof));
+
+        let foreign = other.prove(2).unwrap();
+        assert!(other.verify_proof(&input[3], &foreign));
+        assert!(!mt.verify_proof(&input[3], &foreign));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn deduplicated_leaf_hashing() {
+        use core::cell::Cell;
+
+        let mut input = vec![b"blob".to_vec(); 8];
+        input[2] = b"other".to_vec();
+        input[5] = b"third".to_vec();
+
+        let mt = Sha256Tree::construct_dedup(&input);
+        assert_eq!(mt, Sha256Tree::construct(&input));
+
+        let calls = Cell::new(0);
+        let counted = Sha256Tree::build_memoized(&input, |item| {
+            calls.set(calls.get() + 1);
+            hash_data::<Sha256>(item)
+        });
+        assert_eq!(counted, mt);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn load_verified() {
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let root = Sha256Tree::construct(&input).hash;
+
+        let mt = Sha256Tree::load_verified(&input, &root).unwrap();
+        assert_eq!(mt.root(), &root);
+
+        let actual = Sha256Tree::construct(&input[1..]).hash;
+        assert_eq!(
+            Sha256Tree::load_verified(&input[1..], &root).err(),
+            Some(MerkleError::RootMismatch {
+                expected: root.clone(),
+                actual,
+            })
+        );
+        assert_eq!(
+            Sha256Tree::load_verified(&[], &root).err(),
+            Some(MerkleError::EmptyInput)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn construct_from_stream() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let stream = futures::stream::iter(input.clone());
+        let mt = futures::executor::block_on(Sha256Tree::construct_from_stream(stream));
+        assert_eq!(mt, Sha256Tree::construct(&input));
+    }
+
+    #[test]
+    fn piece_roots() {
+        let input: Vec<_> = (0..16).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let roots = mt.piece_roots(4);
+        assert_eq!(roots.len(), 4);
+        for (root, piece) in roots.iter().zip(input.chunks(4)) {
+            assert_eq!(root, Sha256Tree::construct(piece).root());
+        }
+
+        assert_eq!(mt.piece_roots(16), [mt.root().as_slice()]);
+        assert_eq!(mt.piece_roots(1), mt.leaves().cloned().collect::<Vec<_>>());
+
+        // Pieces line up with subtrees in unbalanced trees too
+        let mt = Sha256Tree::construct(&input[..12]);
+        let roots = mt.piece_roots(4);
+        assert_eq!(roots.len(), 3);
+        assert_eq!(roots[2], Sha256Tree::construct(&input[8..12]).hash);
+    }
+
+    #[test]
+    #[should_panic]
+    fn piece_roots_uneven() {
+        Sha256Tree::construct(&vec![vec![0]; 12]).piece_roots(8);
+    }
+
+    #[test]
+    fn child_accessors() {
+        let input = vec![b"a".to_vec(), b"b".to_vec()];
+        let mt = Sha256Tree::construct(&input);
+
+        let left = mt.left().unwrap();
+        let right = mt.right().unwrap();
+        assert_eq!(left.root(), &hash_data::<Sha256>(&input[0]));
+        assert_eq!(right.root(), &hash_data::<Sha256>(&input[1]));
+
+        assert!(left.left().is_none());
+        assert!(left.right().is_none());
+    }
+
+    #[test]
+    fn split_and_join() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let (left, right) = mt.clone().split().unwrap();
+        assert_eq!(left.leaf_count(), 4);
+        assert_eq!(right.leaf_count(), 4);
+        assert_eq!(left, Sha256Tree::construct(&input[..4]));
+        assert_eq!(Sha256Tree::join(left, right).unwrap().root(), mt.root());
+
+        let leaf = Sha256Tree::construct(&input[..1]);
+        assert_eq!(leaf.clone().split().err(), Some(leaf));
+    }
+
+    #[test]
+    fn retained_leaves() {
+        let input: Vec<_> = (0..6).map(|i| vec![i; 3]).collect();
+
+        let mut mt = Sha256Tree::construct_retaining(&input);
+        assert_eq!(mt.root(), Sha256Tree::construct(&input).root());
+        assert_eq!(mt.get_leaf(3), Some(&input[3]));
+        assert_eq!(mt.get_leaf(6), None);
+        assert_eq!(Sha256Tree::construct(&input).get_leaf(3), None);
+
+        let bundle = mt.prove_data(3).unwrap();
+        assert_eq!(bundle.data.as_ref(), Some(&input[3]));
+        assert!(bundle.verify(mt.root()));
+
+        // Updates keep the retained data in step with the hashes
+        mt.update(3, vec![42]).unwrap();
+        assert_eq!(mt.get_leaf(3), Some(&vec![42]));
+        assert!(mt.prove_data(3).unwrap().verify(mt.root()));
+    }
+
+    #[test]
+    fn standalone_leaf_hashes() {
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+
+        let hashes = leaf_hashes::<Sha256>(&input);
+        assert_eq!(hashes.len(), 5);
+        assert_eq!(hashes[0], hash_data::<Sha256>(&input[0]));
+        assert_eq!(
+            Sha256Tree::from_leaf_hashes(&hashes),
+            Sha256Tree::construct(&input)
+        );
+
+        #[cfg(feature = "rayon")]
+        assert_eq!(super::leaf_hashes_parallel::<Sha256>(&input), hashes);
+    }
+
+    #[test]
+    fn verify_sized() {
+        let input: Vec<_> = (0..6).map(|i| vec![i]).collect();
+        let root = Sha256Tree::construct(&input).hash;
+
+        assert_eq!(Sha256Tree::verify_sized(&input, 6, &root), Ok(true));
+        assert_eq!(Sha256Tree::verify_sized(&input[1..], 5, &root), Ok(false));
+        assert_eq!(
+            Sha256Tree::verify_sized(&input[..5], 6, &root),
+            Err(MerkleError::LengthMismatch {
+                expected: 6,
+                actual: 5
+            })
+        );
+    }
+
+    #[test]
+    fn breadth_first() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let hashes: Vec<_> = mt.iter_bfs().collect();
+        assert_eq!(hashes.len(), mt.node_count());
+        assert_eq!(hashes[0], mt.root());
+        assert_eq!(hashes[1], mt.node_hash(&[false]).unwrap());
+        assert_eq!(hashes[2], mt.node_hash(&[true]).unwrap());
+        assert_eq!(hashes[3..], mt.leaves().collect::<Vec<_>>());
+
+        // The carried leaf of a three-leaf tree sits one level above the others
+        let mt = Sha256Tree::construct(&input[..3]);
+        let hashes: Vec<_> = mt.iter_bfs().collect();
+        assert_eq!(hashes.len(), 5);
+        assert_eq!(hashes[2], mt.node_hash(&[true]).unwrap());
+        assert_eq!(hashes[3], mt.node_hash(&[false, false]).unwrap());
+    }
+
+    #[test]
+    fn flat_round_trip() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        let flat = mt.to_flat().unwrap();
+        assert_eq!(flat.len(), 2 * 8 - 1);
+        assert_eq!(flat[0], mt.hash);
+        assert_eq!(flat[7..], mt.leaves().cloned().collect::<Vec<_>>());
+
+        let restored = Sha256Tree::from_flat(&flat).unwrap();
+        assert_eq!(restored, mt);
+        assert!(restored.validate());
+
+        // Three leaves still form a complete tree, five do not
+        let mt = Sha256Tree::construct(&input[..3]);
+        assert_eq!(Sha256Tree::from_flat(&mt.to_flat().unwrap()).unwrap(), mt);
+        assert_eq!(Sha256Tree::construct(&input[..5]).to_flat(), None);
+    }
+
+    #[test]
+    fn malformed_flat() {
+        let flat: Vec<_> = (0..9).map(|i| vec![i; 32]).collect();
+
+        assert_eq!(
+            Sha256Tree::from_flat(&[]).err(),
+            Some(MerkleError::EmptyInput)
+        );
+        assert_eq!(
+            Sha256Tree::from_flat(&flat[..8]).err(),
+            Some(MerkleError::LengthMismatch {
+                expected: 9,
+                actual: 8
+            })
+        );
+        // A complete tree of 5 leaves has 3 of them below the left child of its root
+        assert_eq!(
+            Sha256Tree::from_flat(&flat).err(),
+            Some(MerkleError::NotPowerOfTwo(3))
+        );
+    }
+
+    #[test]
+    fn matches_root() {
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        assert!(mt.matches_root(mt.root()));
+        assert!(!mt.matches_root(&vec![0; HASH_LEN]));
+        assert!(!mt.matches_root(&mt.root()[1..].to_vec()));
+
+        assert!(Sha256Tree::verify_tree(&mt, mt.root()));
+        assert_eq!(
+            Sha256Tree::verify_tree(&mt, mt.root()),
+            Sha256Tree::verify(&input, mt.root())
+        );
+    }
+
+    #[test]
+    fn length_prefixed_branches() {
+        // Moving a byte across the split keeps the concatenation, and so the plain hash
+        let (a, b) = (vec![1, 2], vec![3]);
+        let (c, d) = (vec![1], vec![2, 3]);
+        assert_eq!(hash_concat::<Sha256>(&a, &b), hash_concat::<Sha256>(&c, &d));
+        assert_ne!(
+            hash_concat_prefixed::<Sha256>(&a, &b),
+            hash_concat_prefixed::<Sha256>(&c, &d)
+        );
+
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct_prefixed(&input);
+        assert_ne!(mt.root(), Sha256Tree::construct(&input).root());
+        assert_eq!(
+            mt.leaves().collect::<Vec<_>>(),
+            Sha256Tree::construct(&input).leaves().collect::<Vec<_>>()
+        );
+
+        for (i, leaf) in input.iter().enumerate() {
+            let proof = mt.prove(i).unwrap();
+            assert!(proof.verify_prefixed(leaf, mt.root()));
+            assert!(!proof.verify(leaf, mt.root()));
+        }
+    }
+
+    #[test]
+    fn proof_len() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        assert_eq!(mt.proof_len(), 3);
+        for i in 0..8 {
+            assert_eq!(mt.prove(i).unwrap().siblings().len(), mt.proof_len());
+        }
+
+        let mt = Sha256Tree::construct(&input[..5]);
+        assert_eq!(mt.proof_len(), 3);
+        assert_eq!(mt.prove(0).unwrap().siblings().len(), 3);
+        assert_eq!(mt.prove(4).unwrap().siblings().len(), 1);
+
+        assert_eq!(Sha256Tree::construct(&input[..1]).proof_len(), 0);
+    }
+
+    #[test]
+    fn from_items() {
+        let items = [7u32, 1, 1 << 20, 42, 0];
+        let mt = Sha256Tree::from_items(&items, |n| n.to_le_bytes().to_vec());
+
+        let input: Vec<_> = items.iter().map(|n| n.to_le_bytes().to_vec()).collect();
+        assert_eq!(mt, Sha256Tree::construct(&input));
+    }
+
+    #[test]
+    fn left_to_right_pairing() {
+        let input: Vec<_> = [b"a", b"b", b"c", b"d"]
+            .iter()
+            .map(|s| s.to_vec())
+            .collect();
+        let h: Vec<_> = input.iter().map(hash_data::<Sha256>).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let ab = hash_concat::<Sha256>(&h[0], &h[1]);
+        let cd = hash_concat::<Sha256>(&h[2], &h[3]);
+        assert_eq!(mt.hash, hash_concat::<Sha256>(&ab, &cd));
+        assert_eq!(
+            mt.root_hex(),
+            "33376a3bd63e9993708a84ddfe6c28ae58b83505dd1fed711bd924ec5a6239f0"
+        );
+
+        // Adjacent equal subtrees are paired in place like any others
+        let repeated = [
+            input[0].clone(),
+            input[1].clone(),
+            input[0].clone(),
+            input[1].clone(),
+        ];
+        let mt = Sha256Tree::construct(&repeated);
+        assert_eq!(mt.hash, hash_concat::<Sha256>(&ab, &ab));
+        assert_eq!(mt.node_hash(&[false]), mt.node_hash(&[true]));
+    }
+
+    #[test]
+    fn try_from_results() {
+        use std::io::{Error, ErrorKind};
+
+        let input: Vec<_> = (0..6).map(|i| vec![i]).collect();
+
+        let results = input.iter().cloned().map(Ok::<_, Error>);
+        let mt = Sha256Tree::try_from_results(results).unwrap();
+        assert_eq!(mt, Some(Sha256Tree::construct(&input)));
+
+        let mut pulled = 0;
+        let results = (0..6).map(|i| {
+            pulled += 1;
+            if i == 3 {
+                Err(Error::new(ErrorKind::UnexpectedEof, "read failed"))
+            } else {
+                Ok(vec![i])
+            }
+        });
+        let error = Sha256Tree::try_from_results(results).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(pulled, 4);
+
+        let results = core::iter::empty::<Result<Vec<u8>, &str>>();
+        assert_eq!(Sha256Tree::try_from_results(results), Ok(None));
+    }
+
+    #[test]
+    fn construct_with_checkpoints() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let (mt, checkpoints) = Sha256Tree::construct_with_checkpoints(&input);
+        assert_eq!(mt, Sha256Tree::construct(&input));
+
+        let sizes: Vec<_> = checkpoints.iter().map(|&(size, _)| size).collect();
+        assert_eq!(sizes, [1, 2, 4, 8]);
+        for (size, hash) in &checkpoints {
+            assert_eq!(hash, Sha256Tree::construct(&input[..*size]).root());
+        }
+        assert_eq!(checkpoints[3].1, mt.hash);
+
+        // Prefixes beyond the last power of two are not checkpoints
+        let (_, checkpoints) = Sha256Tree::construct_with_checkpoints(&input[..7]);
+        assert_eq!(checkpoints.last().unwrap().0, 4);
+    }
+
+    #[test]
+    fn separator_byte() {
+        let (h1, h2) = (hash_data::<Sha256>([1]), hash_data::<Sha256>([2]));
+        assert_eq!(
+            hash_concat_sep::<Sha256>(&h1, &h2, None),
+            hash_concat::<Sha256>(&h1, &h2)
+        );
+
+        let explicit: Vec<u8> = [0x01]
+            .iter()
+            .chain(&h1)
+            .chain(&[0xff])
+            .chain(&h2)
+            .copied()
+            .collect();
+        assert_eq!(
+            hash_concat_sep::<Sha256>(&h1, &h2, Some(0xff)),
+            Sha256::digest(explicit).to_vec()
+        );
+
+        let input: Vec<_> = (0..6).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct_with_separator(&input, 0xff);
+        assert_ne!(mt.root(), Sha256Tree::construct(&input).root());
+        assert_eq!(mt, Sha256Tree::construct_with_separator(&input, 0xff));
+        assert_ne!(mt, Sha256Tree::construct_with_separator(&input, 0xfe));
+        assert_eq!(mt.leaf_count(), 6);
+        assert!(mt.validate());
+    }
+
+    #[test]
+    fn instrumented() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mut events = Vec::new();
+        let mt = Sha256Tree::construct_instrumented(&input, |event| events.push(event));
+        assert_eq!(mt, Sha256Tree::construct(&input));
+
+        let leaves: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::LeafHashed(index) => Some(*index),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(leaves, (0..8).collect::<Vec<_>>());
+
+        let mut levels: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::BranchCreated { level } => Some(*level),
+                _ => None,
+            })
+            .collect();
+        levels.sort();
+        assert_eq!(levels, [1, 1, 1, 1, 2, 2, 3]);
+
+        // A carried leaf creates no branch
+        let mut branches = 0;
+        Sha256Tree::construct_instrumented(&input[..5], |event| {
+            if let Event::BranchCreated { level } = event {
+                branches += 1;
+                assert!(level <= 3);
+            }
+        });
+        assert_eq!(branches, 4);
+    }
+
+    #[test]
+    fn append_proved() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+        let mut mt = Sha256Tree::construct(&input);
+
+        for i in 4..7 {
+            let (root, proof) = mt.append_proved(vec![i]).unwrap();
+            assert_eq!(&root, mt.root());
+            assert!(proof.verify(&vec![i], &root));
+            assert!(!proof.verify(&vec![i + 1], &root));
+        }
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+        assert_eq!(mt, Sha256Tree::construct(&input));
+    }
+
+    #[test]
+    fn perfect() {
+        for len in 1..=17u8 {
+            let input: Vec<_> = (0..len).map(|i| vec![i]).collect();
+            let mt = Sha256Tree::construct(&input);
+            assert_eq!(mt.is_perfect(), len.is_power_of_two(), "{len} leaves");
+        }
+    }
+
+    #[test]
+    fn records() {
+        let ab_c = vec![b"ab".to_vec(), b"c".to_vec()];
+        let a_bc = vec![b"a".to_vec(), b"bc".to_vec()];
+        assert_ne!(record_hash::<Sha256>(&ab_c), record_hash::<Sha256>(&a_bc));
+        assert_ne!(record_hash::<Sha256>(&[]), record_hash::<Sha256>(&[vec![]]));
+
+        let records = vec![ab_c.clone(), vec![b"d".to_vec()]];
+        let mt = Sha256Tree::construct_records(&records);
+        assert_ne!(
+            mt.root(),
+            Sha256Tree::construct_records(&[a_bc, vec![b"d".to_vec()]]).root()
+        );
+
+        let proof = mt.prove(0).unwrap();
+        assert!(proof.verify_leaf_hash(&record_hash::<Sha256>(&ab_c), mt.root()));
+    }
+
+    #[test]
+    fn remove() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mut mt = Sha256Tree::construct_retaining(&input);
+        let old_root = mt.root().clone();
+
+        mt.remove(3).unwrap();
+        assert_ne!(mt.root(), &old_root);
+        assert_eq!(mt.leaf_count(), 8);
+        assert_eq!(mt.get_leaf(3), None);
+        assert!(mt.prove(0).unwrap().verify(&input[0], mt.root()));
+
+        let mut hashes = leaf_hashes::<Sha256>(&input);
+        hashes[3] = Sha256Tree::empty_leaf_hash();
+        assert_eq!(mt.root(), Sha256Tree::from_leaf_hashes(&hashes).root());
+        assert!(mt.prove(3).unwrap().verify_leaf_hash(&hashes[3], mt.root()));
+
+        assert_eq!(
+            mt.remove(8),
+            Err(MerkleError::IndexOutOfRange { index: 8, len: 8 })
+        );
+    }
+
+    #[test]
+    fn padded() {
+        let input: Vec<_> = (1..=5).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct_padded(&input, vec![0]);
+
+        let mut padded = input.clone();
+        padded.resize(8, vec![0]);
+        assert_eq!(mt, Sha256Tree::construct(&padded));
+        assert_eq!(mt.leaf_count(), 8);
+        assert!(mt.is_perfect());
+
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+        assert_eq!(
+            Sha256Tree::construct_padded(&input, vec![0]),
+            Sha256Tree::construct(&input)
+        );
+    }
+
+    #[test]
+    fn levels() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        assert_eq!(mt.level_hashes(0), [mt.root().clone()]);
+        assert_eq!(
+            mt.level_hashes(mt.depth() - 1),
+            leaf_hashes::<Sha256>(&input)
+        );
+        assert_eq!(
+            mt.level_hashes(1),
+            [
+                Sha256Tree::construct(&input[..4]).hash,
+                Sha256Tree::construct(&input[4..]).hash
+            ]
+        );
+        assert!(mt.level_hashes(mt.depth()).is_empty());
+
+        // The carried fifth leaf only shows up one level below the root
+        let mt = Sha256Tree::construct(&input[..5]);
+        assert_eq!(
+            mt.level_hashes(1)[1],
+            leaf_hashes::<Sha256>(&input[4..5])[0]
+        );
+        assert_eq!(mt.level_hashes(3), leaf_hashes::<Sha256>(&input[..4]));
+        let total: usize = (0..mt.depth())
+            .map(|level| mt.level_hashes(level).len())
+            .sum();
+        assert_eq!(total, mt.node_count());
+    }
+
+    #[test]
+    fn verify_iter() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let root = Sha256Tree::construct(&input).hash;
+
+        assert!(Sha256Tree::verify_iter((0..8).map(|i| vec![i]), &root));
+        assert!(!Sha256Tree::verify_iter(
+            (0..8).map(|i| if i == 5 { vec![9] } else { vec![i] }),
+            &root
+        ));
+        assert!(!Sha256Tree::verify_iter((0..7).map(|i| vec![i]), &root));
+        assert!(!Sha256Tree::verify_iter(None, &root));
     }
 
     #[test]
     fn integrity_check() {
         let mut input: Vec<_> = (0..8).map(|i| vec![i]).collect();
 
-        let mt = MerkleTree::construct(&input);
+        let mt = Sha256Tree::construct(&input);
 
         input[0][0] += 1; // Mutate to non-original value
 
-        assert!(!MerkleTree::verify(&input, &mt.hash));
+        assert!(!Sha256Tree::verify(&input, &mt.hash));
     }
 }
@@ -0,0 +1,38 @@
+//! Verification for light clients, which trust a root hash and receive single leaves with
+//! their inclusion proofs, but never hold the whole dataset
+//!
+//! A server that has the data builds the tree and sends each leaf along with its proof,
+//! e.g. in the binary form of [`Proof::to_bytes`]. The client checks them against its root
+//! alone, without rebuilding anything:
+//!
+//! ```
+//! use merkle_tree::light::{self, Proof};
+//! use merkle_tree::Sha256Tree;
+//!
+//! // Server side
+//! let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+//! let tree = Sha256Tree::construct(&input);
+//! let root = tree.root().clone();
+//! let message = (input[5].clone(), tree.prove(5).unwrap().to_bytes());
+//!
+//! // Client side, with only the trusted root
+//! let (leaf, proof) = message;
+//! let proof: Proof = Proof::from_bytes(&proof).unwrap();
+//! assert!(light::verify(&leaf, &proof, &root));
+//! assert!(!light::verify(&vec![42], &proof, &root));
+//! ```
+//!
+//! Verifying an input of whole leaves with [`MerkleTree::verify`](crate::MerkleTree::verify)
+//! instead rebuilds the tree from all of them.
+
+use sha2::Digest;
+
+use crate::{Data, Hash};
+
+pub use crate::proof::{verify_proof_batch, Proof};
+
+/// Verifies that the given leaf data belongs to the tree with the given trusted root hash by
+/// its proof, the same as [`Proof::verify`]
+pub fn verify<D: Digest>(leaf: &Data, proof: &Proof<D>, root_hash: &Hash) -> bool {
+    proof.verify(leaf, root_hash)
+}
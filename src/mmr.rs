@@ -0,0 +1,154 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
+
+use crate::{hash_concat, hash_data, Data, Hash, MerkleTree};
+
+/// A Merkle Mountain Range, an append-only list of perfect trees that keeps every node
+///
+/// Nodes are stored in post-order, so appending never moves an existing node and the
+/// position of a node stays valid forever. The perfect trees, or peaks, follow the set bits
+/// of the leaf count from the largest down. Bagging the peaks from the right gives the same
+/// root as a [`MerkleTree`] over the same leaves.
+pub struct Mmr<D = Sha256> {
+    nodes: Vec<Hash>,
+    leaf_count: u64,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> Mmr<D> {
+    /// Creates an empty range
+    pub fn new() -> Mmr<D> {
+        Mmr {
+            nodes: Vec::new(),
+            leaf_count: 0,
+            digest: PhantomData,
+        }
+    }
+
+    /// Appends a single leaf, merging the peaks it completes, and returns its position
+    pub fn append(&mut self, data: Data) -> u64 {
+        let position = self.nodes.len() as u64;
+        self.nodes.push(hash_data::<D>(&data));
+
+        // Every trailing one bit of the old leaf count is a peak of the same height to merge
+        let mut node = position as usize;
+        for height in 0..self.leaf_count.trailing_ones() {
+            let left = node - ((2 << height) - 1);
+            let parent = hash_concat::<D>(&self.nodes[left], &self.nodes[node]);
+            self.nodes.push(parent);
+            node = self.nodes.len() - 1;
+        }
+        self.leaf_count += 1;
+
+        position
+    }
+
+    /// Number of leaves appended so far
+    pub fn leaf_count(&self) -> u64 {
+        self.leaf_count
+    }
+
+    /// Number of nodes stored, leaves and branches
+    pub fn size(&self) -> u64 {
+        self.nodes.len() as u64
+    }
+
+    /// Hash of the node at the given position, as returned by [`Mmr::append`] for leaves
+    pub fn get(&self, position: u64) -> Option<&Hash> {
+        self.nodes.get(usize::try_from(position).ok()?)
+    }
+
+    /// Root hashes of the perfect trees, from the largest, left-most one
+    pub fn peaks(&self) -> Vec<&Hash> {
+        let mut peaks = Vec::new();
+        let mut offset = 0;
+        for height in (0..u64::BITS).rev() {
+            if self.leaf_count >> height & 1 == 1 {
+                let size = (2 << height) - 1;
+                peaks.push(&self.nodes[offset + size - 1]);
+                offset += size;
+            }
+        }
+        peaks
+    }
+
+    /// Root hash committing to all leaves appended so far, folding the peaks from the right,
+    /// or [`MerkleTree::empty_root`] if there are none
+    pub fn bagged_root(&self) -> Hash {
+        let mut peaks = self.peaks().into_iter().rev();
+        match peaks.next() {
+            Some(lowest) => {
+                peaks.fold(lowest.clone(), |right, left| hash_concat::<D>(left, &right))
+            }
+            None => MerkleTree::<D>::empty_root(),
+        }
+    }
+}
+
+impl<D: Digest> Default for Mmr<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D> fmt::Debug for Mmr<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mmr")
+            .field("nodes", &self.nodes)
+            .field("leaf_count", &self.leaf_count)
+            .finish()
+    }
+}
+
+impl<D> Clone for Mmr<D> {
+    fn clone(&self) -> Self {
+        Mmr {
+            nodes: self.nodes.clone(),
+            leaf_count: self.leaf_count,
+            digest: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::Mmr;
+    use crate::Sha256Tree;
+
+    #[test]
+    fn peaks_follow_leaf_count() {
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+
+        let mut mmr = Mmr::<Sha256>::new();
+        let positions: Vec<_> = input.iter().map(|item| mmr.append(item.clone())).collect();
+        assert_eq!(positions, [0, 1, 3, 4, 7, 8, 10]);
+        assert_eq!(mmr.size(), 11);
+
+        // 7 = 0b111 leaves make peaks of 4, 2 and 1 leaves
+        let peaks = mmr.peaks();
+        assert_eq!(peaks.len(), 7u32.count_ones() as usize);
+        assert_eq!(peaks[0], Sha256Tree::construct(&input[..4]).root());
+        assert_eq!(peaks[1], Sha256Tree::construct(&input[4..6]).root());
+        assert_eq!(peaks[2], mmr.get(10).unwrap());
+
+        let root = mmr.bagged_root();
+        assert_eq!(root, mmr.clone().bagged_root());
+        assert_eq!(&root, Sha256Tree::construct(&input).root());
+
+        mmr.append(vec![7]);
+        assert_eq!(mmr.peaks().len(), 1);
+        assert_ne!(mmr.bagged_root(), root);
+    }
+
+    #[test]
+    fn empty_range() {
+        let mmr = Mmr::<Sha256>::default();
+        assert!(mmr.peaks().is_empty());
+        assert_eq!(mmr.bagged_root(), Sha256Tree::empty_root());
+    }
+}
@@ -0,0 +1,214 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    ct_eq, hash_concat, hash_data, split_point, Data, Hash, MerkleTree, MerkleTreeChildren,
+};
+
+/// An inclusion proof for several leaves of the same tree at once
+///
+/// Holds the roots of all subtrees that contain none of the proven leaves, in left-to-right
+/// order. Together with the leaf count they let the verifier rebuild the tree shape and
+/// hash the proven leaves up to the root, sharing every node their paths have in common.
+pub struct MultiProof<D = Sha256> {
+    /// Leaf count of the tree, which determines its shape
+    pub(crate) leaf_count: usize,
+    /// Hashes of the pruned subtrees, in left-to-right order
+    pub(crate) hashes: Vec<Hash>,
+    pub(crate) digest: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Generates a single proof for all leaves at the given indices
+    /// Returns `None` if no index is given or any of them is out of range
+    pub fn prove_multi(&self, indices: &[usize]) -> Option<MultiProof<D>> {
+        let leaf_count = self.leaf_count();
+
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.is_empty() || indices[indices.len() - 1] >= leaf_count {
+            return None;
+        }
+
+        let mut hashes = Vec::new();
+        self.collect_pruned(&indices, 0, &mut hashes);
+
+        Some(MultiProof {
+            leaf_count,
+            hashes,
+            digest: PhantomData,
+        })
+    }
+
+    /// Generates a single proof for the leaves at the given indices after they were changed,
+    /// e.g. by [`MerkleTree::update`], for a replica to authenticate the new data against the
+    /// current root
+    /// Repeated indices are proven once. `None` if there are no indices or any is out of
+    /// range, like [`MerkleTree::prove_multi`]
+    pub fn changed_proofs(&self, indices: &[usize]) -> Option<MultiProof<D>> {
+        self.prove_multi(indices)
+    }
+
+    /// Collects the roots of subtrees without any of the given sorted indices
+    /// `offset` is the index of the subtree's first leaf
+    fn collect_pruned(&self, indices: &[usize], offset: usize, hashes: &mut Vec<Hash>) {
+        if indices.is_empty() {
+            hashes.push(self.hash.clone());
+            return;
+        }
+
+        if let MerkleTreeChildren::Branch { left, right } = &self.children {
+            let mid = offset + left.leaf_count();
+            let (left_indices, right_indices) =
+                indices.split_at(indices.partition_point(|&i| i < mid));
+            left.collect_pruned(left_indices, offset, hashes);
+            right.collect_pruned(right_indices, mid, hashes);
+        }
+    }
+}
+
+impl<D: Digest> MultiProof<D> {
+    /// Verifies that all given `(index, data)` leaves belong to the tree with the given root hash
+    pub fn verify(&self, leaves: &[(usize, Data)], root: &Hash) -> bool {
+        let mut leaves: Vec<_> = leaves
+            .iter()
+            .map(|(index, data)| (*index, hash_data::<D>(data)))
+            .collect();
+        leaves.sort_unstable_by_key(|(index, _)| *index);
+
+        // Every index must be proven exactly once
+        if leaves.is_empty() || leaves.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return false;
+        }
+
+        let mut hashes = self.hashes.iter();
+        match Self::fold(self.leaf_count, &leaves, 0, &mut hashes) {
+            Some(hash) => hashes.next().is_none() && ct_eq(&hash, root),
+            None => false,
+        }
+    }
+
+    /// Recomputes the root of a subtree of `count` leaves starting at leaf `offset`
+    fn fold<'a>(
+        count: usize,
+        leaves: &[(usize, Hash)],
+        offset: usize,
+        hashes: &mut impl Iterator<Item = &'a Hash>,
+    ) -> Option<Hash> {
+        match leaves {
+            [] => hashes.next().cloned(),
+            [(index, hash)] if count == 1 => (*index == offset).then(|| hash.clone()),
+            _ if count == 1 => None,
+            _ => {
+                let mid = offset + split_point(count);
+                let (left, right) = leaves.split_at(leaves.partition_point(|(i, _)| *i < mid));
+                let left = Self::fold(mid - offset, left, offset, hashes)?;
+                let right = Self::fold(count - (mid - offset), right, mid, hashes)?;
+                Some(hash_concat::<D>(&left, &right))
+            }
+        }
+    }
+}
+
+impl<D> fmt::Debug for MultiProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MultiProof")
+            .field("leaf_count", &self.leaf_count)
+            .field("hashes", &self.hashes)
+            .finish()
+    }
+}
+
+impl<D> Clone for MultiProof<D> {
+    fn clone(&self) -> Self {
+        MultiProof {
+            leaf_count: self.leaf_count,
+            hashes: self.hashes.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialEq for MultiProof<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.leaf_count == other.leaf_count && self.hashes == other.hashes
+    }
+}
+
+impl<D> Eq for MultiProof<D> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::Sha256Tree;
+
+    #[test]
+    fn shared_siblings_are_deduplicated() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        let indices = [0, 1, 4];
+        let proof = mt.prove_multi(&indices).unwrap();
+
+        let leaves: Vec<_> = indices.iter().map(|&i| (i, input[i].clone())).collect();
+        assert!(proof.verify(&leaves, &mt.hash));
+
+        let independent: usize = indices
+            .iter()
+            .map(|&i| mt.prove(i).unwrap().siblings.len())
+            .sum();
+        assert!(proof.hashes.len() < independent);
+    }
+
+    #[test]
+    fn non_power_of_two_tree() {
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        let proof = mt.prove_multi(&[2, 6]).unwrap();
+        assert!(proof.verify(&[(6, input[6].clone()), (2, input[2].clone())], &mt.hash));
+    }
+
+    #[test]
+    fn changed_leaves() {
+        let mut input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mut mt = Sha256Tree::construct(&input);
+
+        for index in [1, 6] {
+            input[index] = vec![0xff, index as u8];
+            mt.update(index, input[index].clone()).unwrap();
+        }
+
+        let proof = mt.changed_proofs(&[6, 1, 6]).unwrap();
+        let changed = [(1, input[1].clone()), (6, input[6].clone())];
+        assert!(proof.verify(&changed, mt.root()));
+        assert_eq!(proof.hashes.len(), 4);
+
+        let stale = [(1, vec![1]), (6, input[6].clone())];
+        assert!(!proof.verify(&stale, mt.root()));
+
+        assert!(mt.changed_proofs(&[]).is_none());
+        assert!(mt.changed_proofs(&[1, 8]).is_none());
+    }
+
+    #[test]
+    fn wrong_leaves() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove_multi(&[0, 1, 4]).unwrap();
+
+        // Mutated data, a swapped position and a missing leaf must all fail
+        assert!(!proof.verify(&[(0, vec![0]), (1, vec![1]), (4, vec![42])], &mt.hash));
+        assert!(!proof.verify(&[(0, vec![0]), (1, vec![1]), (5, vec![4])], &mt.hash));
+        assert!(!proof.verify(&[(0, vec![0]), (1, vec![1])], &mt.hash));
+
+        assert!(mt.prove_multi(&[8]).is_none());
+        assert!(mt.prove_multi(&[]).is_none());
+    }
+}
@@ -0,0 +1,83 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
+
+use crate::{hash_data, Data, Hash, Proof};
+
+/// The part of a Merkle tree that a single inclusion proof authenticates
+///
+/// Holds the hashes of the nodes on the path from a leaf up to the root and of their
+/// siblings, but none of the rest of the tree, so a verifier can cache what a proof told it
+/// without the full tree.
+pub struct PartialTree<D = Sha256> {
+    path: Vec<Hash>,
+    siblings: Vec<Hash>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> PartialTree<D> {
+    /// Reconstructs the nodes authenticated by a proof of the given leaf
+    /// The root is whatever the proof hashes up to, so it must be compared against a trusted
+    /// root hash before the tree is relied on
+    pub fn from_proof(leaf: &Data, proof: &Proof<D>) -> PartialTree<D> {
+        PartialTree {
+            path: proof.ancestors(hash_data::<D>(leaf)),
+            siblings: proof.siblings.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialTree<D> {
+    /// Root hash the reconstructed path leads to
+    pub fn root(&self) -> &Hash {
+        self.path.last().unwrap()
+    }
+
+    /// Hashes of the nodes on the path from the leaf up to the root, both inclusive
+    pub fn path(&self) -> &[Hash] {
+        &self.path
+    }
+
+    /// Hashes of the siblings of the path nodes, from the leaf level upwards
+    pub fn siblings(&self) -> &[Hash] {
+        &self.siblings
+    }
+}
+
+impl<D> fmt::Debug for PartialTree<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PartialTree")
+            .field("path", &self.path)
+            .field("siblings", &self.siblings)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PartialTree, Sha256Tree};
+
+    #[test]
+    fn from_proof() {
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        for (i, leaf) in input.iter().enumerate() {
+            let proof = mt.prove(i).unwrap();
+            let partial = PartialTree::from_proof(leaf, &proof);
+            assert_eq!(partial.root(), mt.root());
+            assert_eq!(partial.path().len(), proof.siblings().len() + 1);
+            assert_eq!(partial.siblings(), proof.siblings());
+            assert_eq!(
+                &partial.path()[0],
+                Sha256Tree::construct(&input[i..=i]).root()
+            );
+        }
+
+        let proof = mt.prove(0).unwrap();
+        assert_ne!(PartialTree::from_proof(&vec![9], &proof).root(), mt.root());
+    }
+}
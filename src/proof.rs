@@ -0,0 +1,1107 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
+
+use crate::encoding::{from_base64, from_hex, to_base64, to_hex};
+use crate::{
+    ct_eq, hash_concat, hash_concat_prefixed, hash_concat_sep, hash_data, split_point, Data, Hash,
+    ProofDecodeError,
+};
+
+/// An inclusion proof for a single leaf of a Merkle tree hashed with `D`
+///
+/// Both vectors are ordered from the leaf level up to the level just below the root.
+///
+/// In text form, each level becomes its direction bit (`0` or `1`) followed by the encoded
+/// sibling hash, and levels are joined by `:`. An empty proof encodes to an empty string.
+///
+/// Proofs have at most [`MAX_PROOF_LEVELS`] levels, whose directions are packed into a
+/// single bitmap.
+///
+/// In binary form, a proof of `n` levels is laid out as
+/// - one byte holding `n`
+/// - `ceil(n / 8)` bytes of direction bits, the leaf level in the least significant bit
+///   of the first byte, with any unused bits zero
+/// - the `n` sibling hashes, from the leaf level upwards
+pub struct Proof<D = Sha256> {
+    /// Hashes of the sibling nodes along the path from the leaf to the root
+    pub(crate) siblings: Vec<Hash>,
+    /// For each level, `true` if the proven node is the right child (its sibling is on the left)
+    pub(crate) directions: Directions,
+    pub(crate) digest: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> Proof<D> {
+    /// Sibling hashes, ordered from the leaf level upwards
+    pub fn siblings(&self) -> &[Hash] {
+        &self.siblings
+    }
+
+    /// Direction bits, ordered from the leaf level upwards
+    /// `true` means the proven node is the right child at that level
+    pub fn directions(&self) -> Vec<bool> {
+        self.directions.iter().collect()
+    }
+
+    /// Encodes the proof as text with hex sibling hashes
+    pub fn to_hex(&self) -> String {
+        self.to_text(to_hex)
+    }
+
+    /// Parses a proof produced by [`Proof::to_hex`]
+    pub fn from_hex(s: &str) -> Result<Proof<D>, ProofDecodeError> {
+        Self::from_text(s, from_hex)
+    }
+
+    /// Encodes the proof as text with base64 sibling hashes
+    pub fn to_base64(&self) -> String {
+        self.to_text(to_base64)
+    }
+
+    /// Parses a proof produced by [`Proof::to_base64`]
+    pub fn from_base64(s: &str) -> Result<Proof<D>, ProofDecodeError> {
+        Self::from_text(s, from_base64)
+    }
+
+    /// Encodes the proof in the compact binary form
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let len = self.siblings.len();
+        let hash_len = <D as Digest>::output_size();
+
+        let mut bytes = Vec::with_capacity(1 + len.div_ceil(8) + len * hash_len);
+        bytes.push(len as u8);
+
+        bytes.extend(&self.directions.bits.to_le_bytes()[..len.div_ceil(8)]);
+
+        for sibling in &self.siblings {
+            bytes.extend(sibling);
+        }
+        bytes
+    }
+
+    /// Parses a proof produced by [`Proof::to_bytes`]
+    pub fn from_bytes(b: &[u8]) -> Result<Proof<D>, ProofDecodeError> {
+        let hash_len = <D as Digest>::output_size();
+
+        let (&len, rest) = b.split_first().ok_or(ProofDecodeError::Truncated)?;
+        let len = usize::from(len);
+        if len > MAX_PROOF_LEVELS {
+            return Err(ProofDecodeError::InvalidEncoding);
+        }
+
+        let bitmap_len = len.div_ceil(8);
+        if rest.len() < bitmap_len + len * hash_len {
+            return Err(ProofDecodeError::Truncated);
+        }
+        if rest.len() > bitmap_len + len * hash_len {
+            return Err(ProofDecodeError::TrailingBytes);
+        }
+
+        let (bitmap, rest) = rest.split_at(bitmap_len);
+        let mut bits = [0u8; 8];
+        bits[..bitmap_len].copy_from_slice(bitmap);
+        let directions = Directions {
+            bits: u64::from_le_bytes(bits),
+            len: len as u8,
+        };
+
+        // Unused direction bits must be zero so that every proof has a single encoding
+        if directions.bits.checked_shr(len as u32).unwrap_or(0) != 0 {
+            return Err(ProofDecodeError::InvalidEncoding);
+        }
+
+        Ok(Proof {
+            siblings: rest.chunks(hash_len).map(<[u8]>::to_vec).collect(),
+            directions,
+            digest: PhantomData,
+        })
+    }
+
+    /// Splits the proof into its sibling hashes concatenated into one buffer and its
+    /// direction bits, both from the leaf level up, e.g. to pass it across an FFI boundary
+    pub fn flatten(&self) -> (Vec<u8>, Vec<bool>) {
+        (self.siblings.concat(), self.directions())
+    }
+
+    /// Reassembles a proof produced by [`Proof::flatten`]
+    /// The buffer must hold one digest-sized hash per direction bit
+    pub fn unflatten(hashes: &[u8], directions: &[bool]) -> Result<Proof<D>, ProofDecodeError> {
+        let hash_len = <D as Digest>::output_size();
+        if !hashes.len().is_multiple_of(hash_len) || directions.len() > MAX_PROOF_LEVELS {
+            return Err(ProofDecodeError::InvalidEncoding);
+        }
+        match (hashes.len() / hash_len).cmp(&directions.len()) {
+            Ordering::Less => return Err(ProofDecodeError::Truncated),
+            Ordering::Greater => return Err(ProofDecodeError::TrailingBytes),
+            Ordering::Equal => {}
+        }
+
+        Ok(Proof {
+            siblings: hashes.chunks(hash_len).map(<[u8]>::to_vec).collect(),
+            directions: directions.iter().copied().collect(),
+            digest: PhantomData,
+        })
+    }
+
+    fn to_text(&self, encode: fn(&[u8]) -> String) -> String {
+        let levels: Vec<_> = self
+            .siblings
+            .iter()
+            .zip(self.directions.iter())
+            .map(|(sibling, is_right)| alloc::format!("{}{}", u8::from(is_right), encode(sibling)))
+            .collect();
+        levels.join(":")
+    }
+
+    fn from_text(
+        s: &str,
+        decode: fn(&str) -> Option<Vec<u8>>,
+    ) -> Result<Proof<D>, ProofDecodeError> {
+        let mut siblings = Vec::new();
+        let mut directions = Directions::default();
+
+        for level in s.split(':').filter(|_| !s.is_empty()) {
+            let is_right = match level.as_bytes().first() {
+                Some(b'0') => false,
+                Some(b'1') => true,
+                _ => return Err(ProofDecodeError::InvalidEncoding),
+            };
+            let sibling = decode(&level[1..]).ok_or(ProofDecodeError::InvalidEncoding)?;
+            if sibling.len() != <D as Digest>::output_size() {
+                return Err(ProofDecodeError::InvalidEncoding);
+            }
+            if directions.len() == MAX_PROOF_LEVELS {
+                return Err(ProofDecodeError::InvalidEncoding);
+            }
+
+            siblings.push(sibling);
+            directions.push(is_right);
+        }
+
+        Ok(Proof {
+            siblings,
+            directions,
+            digest: PhantomData,
+        })
+    }
+
+    /// Verifies that the given leaf data belongs to the tree with the given root hash
+    /// Malformed proofs are rejected before any hashing, see [`Proof::is_well_formed`]
+    pub fn verify(&self, leaf: &Data, root_hash: &Hash) -> bool {
+        self.compute_root(leaf)
+            .is_some_and(|root| ct_eq(&root, root_hash))
+    }
+
+    /// Whether the proof has one direction bit per sibling and every sibling is a hash of
+    /// `D`, i.e. [`HASH_LEN`](crate::HASH_LEN) bytes long for SHA-256
+    pub fn is_well_formed(&self) -> bool {
+        let hash_len = <D as Digest>::output_size();
+        self.siblings.len() == self.directions.len()
+            && self
+                .siblings
+                .iter()
+                .all(|sibling| sibling.len() == hash_len)
+    }
+
+    /// Verifies that the given leaf data belongs to the tree with the given hex encoded root
+    /// hash, as produced by [`MerkleTree::root_hex`](crate::MerkleTree::root_hex)
+    pub fn verify_hex(&self, leaf: &Data, root_hex: &str) -> Result<bool, ProofDecodeError> {
+        let root_hash = from_hex(root_hex).ok_or(ProofDecodeError::InvalidEncoding)?;
+        Ok(self.verify(leaf, &root_hash))
+    }
+
+    /// Root hash of the tree that the given leaf data would belong to by this proof
+    /// `None` if the proof is not [well formed](Proof::is_well_formed)
+    pub fn compute_root(&self, leaf: &Data) -> Option<Hash> {
+        self.climb(hash_data::<D>(leaf), self.siblings.len())
+    }
+
+    /// Verifies that the given leaf data belongs to the tree with the given root hash, built
+    /// by [`MerkleTree::construct_prefixed`](crate::MerkleTree::construct_prefixed)
+    pub fn verify_prefixed(&self, leaf: &Data, root_hash: &Hash) -> bool {
+        let levels = self.siblings.len();
+        let root = self.climb_with(hash_data::<D>(leaf), levels, hash_concat_prefixed::<D>);
+        root.is_some_and(|root| ct_eq(&root, root_hash))
+    }
+
+    /// Lowest node that the paths of both proofs pass through, as its depth below the root,
+    /// `0` for the root itself, and its hash
+    /// Returns `None` if the proofs do not lead their leaves to the same root, or if either
+    /// is not [well formed](Proof::is_well_formed).
+    pub fn common_ancestor(
+        &self,
+        other: &Proof<D>,
+        leaf_a: &Data,
+        leaf_b: &Data,
+    ) -> Option<(usize, Hash)> {
+        if !self.is_well_formed() || !other.is_well_formed() {
+            return None;
+        }
+
+        let path_a = self.ancestors(hash_data::<D>(leaf_a));
+        let path_b = other.ancestors(hash_data::<D>(leaf_b));
+
+        // Both paths are compared from the root down, where they can only part once
+        let shared = path_a
+            .iter()
+            .rev()
+            .zip(path_b.iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let depth = shared.checked_sub(1)?;
+        Some((depth, path_a[path_a.len() - shared].clone()))
+    }
+
+    /// Levels, counted from the leaf level upwards as in [`Proof::siblings`], at which this
+    /// proof and another one for the same leaf position differ in sibling or direction
+    /// Levels that only one of the proofs has count as different.
+    pub fn diff_levels(&self, other: &Proof<D>) -> Vec<usize> {
+        let levels = self.siblings.len().max(other.siblings.len());
+        (0..levels)
+            .filter(|&level| {
+                self.siblings.get(level) != other.siblings.get(level)
+                    || self.directions.iter().nth(level) != other.directions.iter().nth(level)
+            })
+            .collect()
+    }
+
+    /// Hashes of the nodes on the path from the leaf with the given hash up to the root, both
+    /// inclusive
+    pub(crate) fn ancestors(&self, leaf_hash: Hash) -> Vec<Hash> {
+        let mut path = alloc::vec![leaf_hash];
+        for (sibling, is_right) in self.siblings.iter().zip(self.directions.iter()) {
+            let hash = path.last().unwrap();
+            let parent = if is_right {
+                hash_concat::<D>(sibling, hash)
+            } else {
+                hash_concat::<D>(hash, sibling)
+            };
+            path.push(parent);
+        }
+        path
+    }
+
+    /// Verifies that the given leaf data belongs to the tree with the given root hash, built
+    /// by [`MerkleTree::construct_with_separator`](crate::MerkleTree::construct_with_separator)
+    /// with the given separator byte
+    pub fn verify_with_separator(&self, leaf: &Data, root_hash: &Hash, separator: u8) -> bool {
+        let levels = self.siblings.len();
+        let merge = |left: &Hash, right: &Hash| hash_concat_sep::<D>(left, right, Some(separator));
+        let root = self.climb_with(hash_data::<D>(leaf), levels, merge);
+        root.is_some_and(|root| ct_eq(&root, root_hash))
+    }
+
+    /// Verifies that the leaf with the given hash belongs to the tree with the given root hash,
+    /// skipping the leaf hashing step as [`MerkleTree::from_leaf_hashes`] does
+    ///
+    /// [`MerkleTree::from_leaf_hashes`]: crate::MerkleTree::from_leaf_hashes
+    pub fn verify_leaf_hash(&self, leaf_hash: &Hash, root_hash: &Hash) -> bool {
+        let root = self.climb(leaf_hash.clone(), self.siblings.len());
+        root.is_some_and(|root| ct_eq(&root, root_hash))
+    }
+
+    /// Verifies that the given leaf data is at the given index of the tree with the given leaf
+    /// count and root hash, by also checking the directions against the path to that index
+    ///
+    /// The expected path follows from the index and the tree size as in RFC 6962, section
+    /// 2.1.1: the left subtree of every branch holds the largest power of two of leaves smaller
+    /// than the branch's leaf count. The size must be a trusted value, such as the one a
+    /// signed tree head commits to.
+    pub fn verify_at(&self, leaf: &Data, index: usize, tree_size: usize, root_hash: &Hash) -> bool {
+        self.verify_at_with(leaf, index, tree_size, root_hash, BitOrder::default())
+    }
+
+    /// Verifies that the given leaf data is at the given index of the tree with the given leaf
+    /// count and root hash, like [`Proof::verify_at`], reading the index in the given bit order
+    /// Bit-reversed [`BitOrder::LsbFirst`] indices are only defined in trees with a power of
+    /// two of leaves, so other sizes never verify in that order.
+    pub fn verify_at_with(
+        &self,
+        leaf: &Data,
+        index: usize,
+        tree_size: usize,
+        root_hash: &Hash,
+        order: BitOrder,
+    ) -> bool {
+        if index >= tree_size {
+            return false;
+        }
+        let index = match order {
+            BitOrder::MsbFirst => index,
+            BitOrder::LsbFirst if tree_size.is_power_of_two() => {
+                let levels = tree_size.trailing_zeros();
+                index
+                    .reverse_bits()
+                    .checked_shr(usize::BITS - levels)
+                    .unwrap_or(0)
+            }
+            BitOrder::LsbFirst => return false,
+        };
+
+        self.directions == path_directions(index, tree_size) && self.verify(leaf, root_hash)
+    }
+
+    /// Verifies that the given leaf data lies below the trusted node with the given hash,
+    /// `levels` steps up from the leaf
+    /// Only the first `levels` siblings are used, so a proof verifies up to any of its ancestors
+    pub fn verify_to(&self, leaf: &Data, target_hash: &Hash, levels: usize) -> bool {
+        levels <= self.siblings.len()
+            && self
+                .climb(hash_data::<D>(leaf), levels)
+                .is_some_and(|hash| ct_eq(&hash, target_hash))
+    }
+
+    /// Verifies a proof whose sibling hashes arrive from `reader`, one per direction bit and
+    /// from the leaf level up, without buffering more than one of them
+    /// Fails with `UnexpectedEof` if the reader ends before the last sibling
+    #[cfg(feature = "std")]
+    pub fn verify_stream<R: std::io::Read>(
+        leaf: &Data,
+        directions: &[bool],
+        mut reader: R,
+        root_hash: &Hash,
+    ) -> std::io::Result<bool> {
+        let mut sibling = alloc::vec![0; <D as Digest>::output_size()];
+        let mut hash = hash_data::<D>(leaf);
+        for &is_right in directions {
+            reader.read_exact(&mut sibling)?;
+            hash = if is_right {
+                hash_concat::<D>(&sibling, &hash)
+            } else {
+                hash_concat::<D>(&hash, &sibling)
+            };
+        }
+
+        Ok(ct_eq(&hash, root_hash))
+    }
+
+    /// Hash of the ancestor `levels` steps up from the leaf with the given leaf hash, `None`
+    /// if the proof is malformed
+    fn climb(&self, leaf_hash: Hash, levels: usize) -> Option<Hash> {
+        self.climb_with(leaf_hash, levels, hash_concat::<D>)
+    }
+
+    /// Like [`Proof::climb`], hashing branches with `merge`
+    fn climb_with(
+        &self,
+        leaf_hash: Hash,
+        levels: usize,
+        merge: impl Fn(&Hash, &Hash) -> Hash,
+    ) -> Option<Hash> {
+        if !self.is_well_formed() {
+            return None;
+        }
+
+        let steps = self
+            .siblings
+            .iter()
+            .zip(self.directions.iter())
+            .take(levels);
+        let root = steps.fold(leaf_hash, |hash, (sibling, is_right)| {
+            if is_right {
+                merge(sibling, &hash)
+            } else {
+                merge(&hash, sibling)
+            }
+        });
+        Some(root)
+    }
+}
+
+/// Order in which the bits of a leaf index select the directions on its path
+///
+/// Implementations differ in whether the branch at the root is chosen by the most or the
+/// least significant bit of the index, which matters when proofs are bound to positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitOrder {
+    /// The most significant bit chooses at the root and the least significant one at the
+    /// leaf, so that leaves are numbered from left to right as in RFC 6962
+    #[default]
+    MsbFirst,
+    /// The least significant bit chooses at the root, numbering leaves in bit-reversed order
+    LsbFirst,
+}
+
+/// Directions of the path from the leaf at an in-range index of a tree with the given leaf
+/// count up to the root, as its proof holds them
+fn path_directions(mut index: usize, mut count: usize) -> Directions {
+    let mut from_root = Vec::new();
+    while count > 1 {
+        let left_count = split_point(count);
+        let is_right = index >= left_count;
+        if is_right {
+            index -= left_count;
+            count -= left_count;
+        } else {
+            count = left_count;
+        }
+        from_root.push(is_right);
+    }
+    from_root.into_iter().rev().collect()
+}
+
+/// Maximum number of levels in a [`Proof`]
+pub const MAX_PROOF_LEVELS: usize = u64::BITS as usize;
+
+/// Direction bits of a proof, packed from the leaf level up starting at the least
+/// significant bit, with any unused bits zero
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Directions {
+    bits: u64,
+    len: u8,
+}
+
+impl Directions {
+    pub(crate) fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Appends the direction of the next level up
+    /// Panics if the proof already has [`MAX_PROOF_LEVELS`] levels
+    pub(crate) fn push(&mut self, is_right: bool) {
+        assert!(self.len() < MAX_PROOF_LEVELS, "proof is too long");
+        self.bits |= u64::from(is_right) << self.len;
+        self.len += 1;
+    }
+
+    /// Flips the direction at the given level
+    #[cfg(test)]
+    pub(crate) fn flip(&mut self, level: usize) {
+        assert!(level < self.len());
+        self.bits ^= 1 << level;
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(|level| self.bits >> level & 1 == 1)
+    }
+}
+
+impl Extend<bool> for Directions {
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|is_right| self.push(is_right));
+    }
+}
+
+impl FromIterator<bool> for Directions {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut directions = Directions::default();
+        directions.extend(iter);
+        directions
+    }
+}
+
+/// Verifies that every leaf belongs to the tree with the given root hash by its paired proof
+/// Stops at the first pair that fails
+pub fn verify_proof_batch<D: Digest>(items: &[(Data, Proof<D>)], root: &Hash) -> bool {
+    items.iter().all(|(leaf, proof)| proof.verify(leaf, root))
+}
+
+/// A leaf index bundled with its inclusion proof and, if the tree retains it, its data
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound = "D: Digest"))]
+pub struct ProvenLeaf<D = Sha256> {
+    pub index: usize,
+    pub data: Option<Data>,
+    pub proof: Proof<D>,
+}
+
+impl<D: Digest> ProvenLeaf<D> {
+    /// Verifies that the bundled data belongs to the tree with the given root hash
+    /// Fails if the bundle has no data
+    pub fn verify(&self, root_hash: &Hash) -> bool {
+        self.data
+            .as_ref()
+            .is_some_and(|data| self.proof.verify(data, root_hash))
+    }
+}
+
+impl<D> fmt::Debug for Proof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Proof")
+            .field("siblings", &self.siblings)
+            .field("directions", &self.directions.iter().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<D> Clone for Proof<D> {
+    fn clone(&self) -> Self {
+        Proof {
+            siblings: self.siblings.clone(),
+            directions: self.directions,
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialEq for Proof<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.siblings == other.siblings && self.directions == other.directions
+    }
+}
+
+impl<D> Eq for Proof<D> {}
+
+impl<D> fmt::Debug for ProvenLeaf<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProvenLeaf")
+            .field("index", &self.index)
+            .field("data", &self.data)
+            .field("proof", &self.proof)
+            .finish()
+    }
+}
+
+impl<D> Clone for ProvenLeaf<D> {
+    fn clone(&self) -> Self {
+        ProvenLeaf {
+            index: self.index,
+            data: self.data.clone(),
+            proof: self.proof.clone(),
+        }
+    }
+}
+
+impl<D> PartialEq for ProvenLeaf<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.data == other.data && self.proof == other.proof
+    }
+}
+
+impl<D> Eq for ProvenLeaf<D> {}
+
+/// Proofs serialize as their hex text form in human-readable formats and as their binary
+/// form otherwise
+#[cfg(feature = "serde")]
+impl<D: Digest> serde::Serialize for Proof<D> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_hex())
+        } else {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, D: Digest> serde::Deserialize<'de> for Proof<D> {
+    fn deserialize<De: serde::Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        use serde::de::Error;
+
+        let proof = if deserializer.is_human_readable() {
+            Proof::from_hex(&String::deserialize(deserializer)?)
+        } else {
+            Proof::from_bytes(&crate::hex_serde::deserialize(deserializer)?)
+        };
+        proof.map_err(De::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use crate::{
+        hash_data, verify_proof_batch, BitOrder, Proof, ProofDecodeError, Sha256Tree, HASH_LEN,
+    };
+
+    #[test]
+    fn valid_proof() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        for (i, leaf) in input.iter().enumerate() {
+            assert!(mt.prove(i).unwrap().verify(leaf, &mt.hash));
+        }
+    }
+
+    #[test]
+    fn mutated_leaf() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(3).unwrap();
+
+        assert!(!proof.verify(&vec![42], &mt.hash));
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(5).unwrap();
+
+        let hex = proof.to_hex();
+        let parsed = Proof::from_hex(&hex).unwrap();
+        assert_eq!(parsed, proof);
+        assert!(parsed.verify(&input[5], &mt.hash));
+
+        let parsed = Proof::from_base64(&proof.to_base64()).unwrap();
+        assert_eq!(parsed, proof);
+
+        // A single-leaf tree has an empty proof
+        let mt = Sha256Tree::construct(&input[..1]);
+        let proof = mt.prove(0).unwrap();
+        assert_eq!(Proof::from_hex(&proof.to_hex()).unwrap(), proof);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(6).unwrap();
+
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes.len(), 1 + 1 + 3 * 32);
+
+        let parsed = Proof::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, proof);
+        assert!(parsed.verify(&input[6], &mt.hash));
+    }
+
+    #[test]
+    fn packed_directions() {
+        let input: Vec<_> = (0..64u8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(45).unwrap();
+        assert_eq!(proof.directions().len(), 6);
+
+        // Six direction bits share one byte after the length
+        let bytes = proof.to_bytes();
+        assert_eq!(bytes.len(), 1 + 1 + 6 * 32);
+        assert_eq!(bytes[1], 45);
+
+        let parsed = Proof::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, proof);
+        assert!(parsed.verify(&input[45], &mt.hash));
+
+        // Longer proofs than the bitmap holds are rejected
+        let mut bytes = vec![65];
+        bytes.extend([0; 9]);
+        bytes.extend(vec![0; 65 * 32]);
+        assert_eq!(
+            Proof::<Sha256>::from_bytes(&bytes).err(),
+            Some(ProofDecodeError::InvalidEncoding)
+        );
+    }
+
+    #[test]
+    fn flat_round_trip() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(6).unwrap();
+
+        let (hashes, directions) = proof.flatten();
+        assert_eq!(hashes.len(), 3 * HASH_LEN);
+        assert_eq!(directions, proof.directions());
+
+        let parsed = Proof::unflatten(&hashes, &directions).unwrap();
+        assert_eq!(parsed, proof);
+        assert!(parsed.verify(&input[6], &mt.hash));
+    }
+
+    #[test]
+    fn malformed_flat() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let (hashes, directions) = mt.prove(6).unwrap().flatten();
+
+        assert_eq!(
+            Proof::<Sha256>::unflatten(&hashes[1..], &directions).err(),
+            Some(ProofDecodeError::InvalidEncoding)
+        );
+        assert_eq!(
+            Proof::<Sha256>::unflatten(&hashes[HASH_LEN..], &directions).err(),
+            Some(ProofDecodeError::Truncated)
+        );
+        assert_eq!(
+            Proof::<Sha256>::unflatten(&hashes, &directions[1..]).err(),
+            Some(ProofDecodeError::TrailingBytes)
+        );
+    }
+
+    #[test]
+    fn malformed_bytes() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let mut bytes = mt.prove(6).unwrap().to_bytes();
+
+        assert_eq!(
+            Proof::<Sha256>::from_bytes(&bytes[..bytes.len() - 1]).err(),
+            Some(ProofDecodeError::Truncated)
+        );
+        assert_eq!(
+            Proof::<Sha256>::from_bytes(&[]).err(),
+            Some(ProofDecodeError::Truncated)
+        );
+
+        bytes.push(0);
+        assert_eq!(
+            Proof::<Sha256>::from_bytes(&bytes).err(),
+            Some(ProofDecodeError::TrailingBytes)
+        );
+
+        bytes.pop();
+        bytes[1] |= 0x80;
+        assert_eq!(
+            Proof::<Sha256>::from_bytes(&bytes).err(),
+            Some(ProofDecodeError::InvalidEncoding)
+        );
+    }
+
+    #[test]
+    fn malformed_text() {
+        assert_eq!(
+            Proof::<Sha256>::from_hex("2abcd").err(),
+            Some(ProofDecodeError::InvalidEncoding)
+        );
+        assert_eq!(
+            Proof::<Sha256>::from_hex("1abc").err(),
+            Some(ProofDecodeError::InvalidEncoding)
+        );
+        assert_eq!(
+            Proof::<Sha256>::from_hex("1ab::0cd").err(),
+            Some(ProofDecodeError::InvalidEncoding)
+        );
+        // Siblings must be exactly one digest long
+        assert_eq!(
+            Proof::<Sha256>::from_hex("1abcd").err(),
+            Some(ProofDecodeError::InvalidEncoding)
+        );
+    }
+
+    #[test]
+    fn swapped_sibling() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let mut proof = mt.prove(3).unwrap();
+
+        proof.siblings.swap(0, 1);
+        assert!(!proof.verify(&input[3], &mt.hash));
+
+        // Correct siblings with a flipped direction bit must fail too
+        let mut proof = mt.prove(3).unwrap();
+        proof.directions.flip(0);
+        assert!(!proof.verify(&input[3], &mt.hash));
+    }
+
+    #[test]
+    fn verify_at_index() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(3).unwrap();
+
+        assert!(proof.verify_at(&input[3], 3, 8, &mt.hash));
+        assert!(!proof.verify_at(&input[3], 5, 8, &mt.hash));
+        assert!(!proof.verify_at(&input[3], 3 + 8, 8, &mt.hash));
+        assert!(!proof.verify_at(&input[3], 3, 16, &mt.hash));
+        assert!(!proof.verify_at(&input[5], 5, 8, &mt.hash));
+
+        for (i, leaf) in input.iter().enumerate() {
+            assert!(mt.prove(i).unwrap().verify_at(leaf, i, 8, &mt.hash));
+        }
+    }
+
+    #[test]
+    fn verify_at_index_unbalanced() {
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+
+        for size in [3, 5, 6, 7] {
+            let mt = Sha256Tree::construct(&input[..size]);
+            for (i, leaf) in input[..size].iter().enumerate() {
+                let proof = mt.prove(i).unwrap();
+                for index in 0..size + 1 {
+                    assert_eq!(
+                        proof.verify_at(leaf, index, size, &mt.hash),
+                        index == i,
+                        "leaf {i} claimed at {index} of {size}"
+                    );
+                }
+            }
+        }
+
+        // The carried leaf of a 5-leaf tree reads as index 1 by its direction bits alone
+        let mt = Sha256Tree::construct(&input[..5]);
+        let proof = mt.prove(4).unwrap();
+        assert!(proof.verify_at(&input[4], 4, 5, &mt.hash));
+        assert!(!proof.verify_at(&input[4], 1, 5, &mt.hash));
+    }
+
+    #[test]
+    fn bit_orders() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+
+        // Leaf 3 (0b011) is leaf 6 (0b110) when the root takes the least significant bit
+        let proof = mt.prove(3).unwrap();
+        assert!(proof.verify_at_with(&input[3], 3, 8, &mt.hash, BitOrder::MsbFirst));
+        assert!(!proof.verify_at_with(&input[3], 6, 8, &mt.hash, BitOrder::MsbFirst));
+        assert!(proof.verify_at_with(&input[3], 6, 8, &mt.hash, BitOrder::LsbFirst));
+        assert!(!proof.verify_at_with(&input[3], 3, 8, &mt.hash, BitOrder::LsbFirst));
+
+        let proof = mt.prove(1).unwrap();
+        assert!(proof.verify_at_with(&input[1], 1, 8, &mt.hash, BitOrder::MsbFirst));
+        assert!(proof.verify_at_with(&input[1], 4, 8, &mt.hash, BitOrder::LsbFirst));
+        assert!(!proof.verify_at_with(&input[1], 4, 8, &mt.hash, BitOrder::MsbFirst));
+
+        let mt = Sha256Tree::construct(&input[..6]);
+        let proof = mt.prove(1).unwrap();
+        assert!(proof.verify_at_with(&input[1], 1, 6, &mt.hash, BitOrder::MsbFirst));
+        assert!(!proof.verify_at_with(&input[1], 4, 6, &mt.hash, BitOrder::LsbFirst));
+    }
+
+    #[test]
+    fn compute_root() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(6).unwrap();
+
+        assert_eq!(proof.compute_root(&input[6]), Some(mt.hash.clone()));
+        assert_ne!(proof.compute_root(&vec![42]), Some(mt.hash));
+    }
+
+    #[test]
+    fn verify_hex_root() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(3).unwrap();
+
+        assert_eq!(proof.verify_hex(&input[3], &mt.root_hex()), Ok(true));
+        assert_eq!(proof.verify_hex(&input[4], &mt.root_hex()), Ok(false));
+        assert_eq!(
+            proof.verify_hex(&input[3], &mt.root_hex().to_uppercase()),
+            Ok(true)
+        );
+
+        assert_eq!(
+            proof.verify_hex(&input[3], "not hex"),
+            Err(ProofDecodeError::InvalidEncoding)
+        );
+        assert_eq!(
+            proof.verify_hex(&input[3], &mt.root_hex()[1..]),
+            Err(ProofDecodeError::InvalidEncoding)
+        );
+    }
+
+    #[test]
+    fn verify_leaf_hash() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        for (i, leaf) in input.iter().enumerate() {
+            let proof = mt.prove(i).unwrap();
+            let leaf_hash = hash_data::<Sha256>(leaf);
+            assert!(proof.verify_leaf_hash(&leaf_hash, &mt.hash));
+            assert_eq!(
+                proof.verify_leaf_hash(&leaf_hash, &mt.hash),
+                proof.verify(leaf, &mt.hash)
+            );
+        }
+
+        // Raw data is not mistaken for its hash
+        let proof = mt.prove(2).unwrap();
+        assert!(!proof.verify_leaf_hash(&input[2], &mt.hash));
+
+        let hashes: Vec<_> = (0..5).map(|i| vec![i; 32]).collect();
+        let mt = Sha256Tree::from_leaf_hashes(&hashes);
+        assert!(mt.prove(4).unwrap().verify_leaf_hash(&hashes[4], &mt.hash));
+    }
+
+    #[test]
+    fn common_ancestor() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = |i| mt.prove(i).unwrap();
+
+        // Leaves 1 and 2 both lie in the left half, whose root is one level below the root
+        let ancestor = proof(1).common_ancestor(&proof(2), &input[1], &input[2]);
+        assert_eq!(ancestor, Some((1, mt.node_hash(&[false]).unwrap().clone())));
+
+        let ancestor = proof(4).common_ancestor(&proof(5), &input[4], &input[5]);
+        assert_eq!(
+            ancestor,
+            Some((2, mt.node_hash(&[true, false]).unwrap().clone()))
+        );
+
+        let ancestor = proof(0).common_ancestor(&proof(7), &input[0], &input[7]);
+        assert_eq!(ancestor, Some((0, mt.hash.clone())));
+
+        // Paths that do not reach the same root share nothing
+        assert_eq!(
+            proof(0).common_ancestor(&proof(1), &input[0], &input[2]),
+            None
+        );
+
+        // A truncated sibling is rejected rather than folded
+        let mut short = proof(1);
+        short.siblings[2].pop();
+        assert_eq!(short.common_ancestor(&proof(2), &input[1], &input[2]), None);
+        assert_eq!(proof(2).common_ancestor(&short, &input[2], &input[1]), None);
+        // An extra sibling without a direction is not silently dropped either
+        let mut extra = proof(1);
+        extra.siblings.push(mt.hash.clone());
+        assert_eq!(extra.common_ancestor(&proof(2), &input[1], &input[2]), None);
+    }
+
+    #[test]
+    fn diff_levels() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mut mt = Sha256Tree::construct(&input);
+        let before = mt.prove(0).unwrap();
+        assert!(before.diff_levels(&before).is_empty());
+
+        mt.update(1, vec![9]).unwrap();
+        let after = mt.prove(0).unwrap();
+        assert_eq!(before.diff_levels(&after), [0]);
+
+        mt.update(7, vec![9]).unwrap();
+        assert_eq!(before.diff_levels(&mt.prove(0).unwrap()), [0, 2]);
+
+        let short = Sha256Tree::construct(&input[..2]).prove(0).unwrap();
+        assert_eq!(before.diff_levels(&short), [1, 2]);
+    }
+
+    #[test]
+    fn malformed() {
+        let input: Vec<_> = (0..4).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(1).unwrap();
+        assert!(proof.is_well_formed());
+
+        let mut extra = proof.clone();
+        extra.siblings.push(mt.root().clone());
+        assert!(!extra.is_well_formed());
+        assert!(!extra.verify(&input[1], mt.root()));
+        assert_eq!(extra.compute_root(&input[1]), None);
+        assert!(!extra.verify_leaf_hash(&hash_data::<Sha256>(&input[1]), mt.root()));
+        assert!(!extra.verify_to(&input[1], mt.root(), 2));
+
+        // Every scheme refuses the extra sibling, even one that verifies without it
+        let prefixed = Sha256Tree::construct_prefixed(&input);
+        let mut extra = prefixed.prove(1).unwrap();
+        assert!(extra.verify_prefixed(&input[1], prefixed.root()));
+        extra.siblings.push(prefixed.root().clone());
+        assert!(!extra.verify_prefixed(&input[1], prefixed.root()));
+        let separated = Sha256Tree::construct_with_separator(&input, 0xff);
+        let mut extra = separated.prove(1).unwrap();
+        assert!(extra.verify_with_separator(&input[1], separated.root(), 0xff));
+        extra.siblings.push(separated.root().clone());
+        assert!(!extra.verify_with_separator(&input[1], separated.root(), 0xff));
+
+        let mut missing = proof.clone();
+        missing.siblings.pop();
+        assert!(!missing.is_well_formed());
+        assert!(!missing.verify(&input[1], mt.root()));
+
+        let mut short = proof;
+        short.siblings[0].pop();
+        assert!(!short.is_well_formed());
+        assert!(!short.verify(&input[1], mt.root()));
+    }
+
+    #[test]
+    fn separated_branches() {
+        let input: Vec<_> = (0..6).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct_with_separator(&input, 0xff);
+        for (i, leaf) in input.iter().enumerate() {
+            let proof = mt.prove(i).unwrap();
+            assert!(proof.verify_with_separator(leaf, &mt.hash, 0xff));
+            assert!(!proof.verify_with_separator(leaf, &mt.hash, 0xfe));
+            assert!(!proof.verify(leaf, &mt.hash));
+        }
+    }
+
+    #[test]
+    fn duplicate_leaves() {
+        let input = vec![b"x".to_vec(), b"x".to_vec(), b"y".to_vec(), b"z".to_vec()];
+
+        let mt = Sha256Tree::construct(&input);
+        let first = mt.prove(0).unwrap();
+        let second = mt.prove(1).unwrap();
+
+        assert!(first.verify(&input[0], &mt.hash));
+        assert!(second.verify(&input[0], &mt.hash));
+        // Each leaf's sibling is the other copy, so only the directions tell the paths apart
+        assert_ne!(first, second);
+        assert_eq!(first.siblings(), second.siblings());
+        assert_ne!(first.directions(), second.directions());
+
+        assert_eq!(mt.index_of(&input[1]), Some(0));
+    }
+
+    #[test]
+    fn verify_to_checkpoint() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(5).unwrap();
+
+        // Leaf 5 is in the left pair of the right half, whose root is its grandparent
+        let grandparent = mt.node_hash(&[true]).unwrap();
+        assert!(proof.verify_to(&input[5], grandparent, 2));
+        assert!(!proof.verify_to(&input[5], grandparent, 1));
+        assert!(!proof.verify_to(&input[4], grandparent, 2));
+
+        assert!(proof.verify_to(&input[5], &mt.hash, 3));
+        assert!(!proof.verify_to(&input[5], &mt.hash, 4));
+    }
+
+    #[test]
+    fn batch_verification() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let mut items: Vec<_> = [1, 4, 6]
+            .into_iter()
+            .map(|i| (input[i].clone(), mt.prove(i).unwrap()))
+            .collect();
+        assert!(verify_proof_batch(&items, &mt.hash));
+        assert!(verify_proof_batch::<Sha256>(&[], &mt.hash));
+
+        items[1].1.siblings[0][0] ^= 1;
+        assert!(!verify_proof_batch(&items, &mt.hash));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn streamed_siblings() {
+        use std::io::{Cursor, ErrorKind};
+
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(5).unwrap();
+        let stream = proof.siblings.concat();
+
+        let verify = |leaf, bytes: &[u8]| {
+            Proof::<Sha256>::verify_stream(leaf, &proof.directions(), Cursor::new(bytes), &mt.hash)
+        };
+        assert!(verify(&input[5], &stream).unwrap());
+        assert!(!verify(&input[4], &stream).unwrap());
+        assert_eq!(
+            proof.verify(&input[5], &mt.hash),
+            verify(&input[5], &stream).unwrap()
+        );
+
+        let error = verify(&input[5], &stream[..stream.len() - 1]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,71 @@
+//! Property tests of the invariants tying construction, proofs and verification together
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::{Data, Sha256Tree};
+
+/// Inputs of `2^k` random leaves for `k` up to 5
+fn perfect_input() -> impl Strategy<Value = Vec<Data>> {
+    (0..=5usize).prop_flat_map(|k| vec(vec(any::<u8>(), 0..16), 1 << k))
+}
+
+/// Inputs of any number of random leaves up to 40, most of them not a power of two
+fn any_input() -> impl Strategy<Value = Vec<Data>> {
+    vec(vec(any::<u8>(), 0..16), 1..=40)
+}
+
+/// A power of two sized input with an in-range leaf index
+fn input_and_index() -> impl Strategy<Value = (Vec<Data>, usize)> {
+    with_index(perfect_input())
+}
+
+/// An input of any size with an in-range leaf index
+fn any_input_and_index() -> impl Strategy<Value = (Vec<Data>, usize)> {
+    with_index(any_input())
+}
+
+/// Pairs every input with an in-range leaf index
+fn with_index(
+    inputs: impl Strategy<Value = Vec<Data>>,
+) -> impl Strategy<Value = (Vec<Data>, usize)> {
+    inputs.prop_flat_map(|input| {
+        let len = input.len();
+        (Just(input), 0..len)
+    })
+}
+
+proptest! {
+    #[test]
+    fn construction_verifies(input in any_input()) {
+        let mt = Sha256Tree::construct(&input);
+        prop_assert!(Sha256Tree::verify(&input, mt.root()));
+    }
+
+    #[test]
+    fn proofs_verify((input, index) in any_input_and_index(), other in 0..40usize) {
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(index).unwrap();
+        prop_assert!(proof.verify(&input[index], mt.root()));
+        prop_assert!(proof.verify_at(&input[index], index, input.len(), mt.root()));
+        prop_assert_eq!(
+            proof.verify_at(&input[index], other, input.len(), mt.root()),
+            other == index
+        );
+    }
+
+    #[test]
+    fn mutated_leaf_fails(
+        (mut input, index) in input_and_index(),
+        replacement in vec(any::<u8>(), 0..16),
+    ) {
+        prop_assume!(input[index] != replacement);
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove(index).unwrap();
+        prop_assert!(!proof.verify(&replacement, mt.root()));
+
+        input[index] = replacement;
+        prop_assert!(!Sha256Tree::verify(&input, mt.root()));
+    }
+}
@@ -0,0 +1,252 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Range;
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    ct_eq, hash_concat, hash_data, split_point, Data, Hash, MerkleTree, MerkleTreeChildren,
+};
+
+/// An inclusion proof for a contiguous range of leaves of the same tree
+///
+/// Holds the roots of the subtrees left and right of the range, in left-to-right order.
+/// Subtrees inside the range are rebuilt from the proven leaves, so no interior hash is
+/// included and the proof only grows with the height of the tree.
+pub struct RangeProof<D = Sha256> {
+    /// Leaf count of the tree, which determines its shape
+    pub(crate) leaf_count: usize,
+    /// Hashes of the subtrees outside the range, in left-to-right order
+    pub(crate) hashes: Vec<Hash>,
+    pub(crate) digest: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Generates a single proof for the leaves at indices `start..end`
+    /// Returns `None` if the range is empty or runs past the last leaf
+    pub fn prove_range(&self, start: usize, end: usize) -> Option<RangeProof<D>> {
+        let leaf_count = self.leaf_count();
+        if start >= end || end > leaf_count {
+            return None;
+        }
+
+        let mut hashes = Vec::new();
+        self.collect_outside(&(start..end), 0, &mut hashes);
+
+        Some(RangeProof {
+            leaf_count,
+            hashes,
+            digest: PhantomData,
+        })
+    }
+
+    /// Roots of the fewest subtrees whose leaves are exactly those at indices `start..end`, in
+    /// left-to-right order, each with its path from the root, `true` for a right child
+    /// Returns no subtrees if the range is empty or runs past the last leaf
+    pub fn range_cover(&self, start: usize, end: usize) -> Vec<(Vec<bool>, Hash)> {
+        let leaf_count = self.leaf_count();
+        let mut cover = Vec::new();
+        if start < end && end <= leaf_count {
+            let mut path = Vec::new();
+            self.collect_cover(&(start..end), 0, leaf_count, &mut path, &mut cover);
+        }
+        cover
+    }
+
+    /// Collects the roots of the largest subtrees that lie entirely inside the given range
+    /// `offset` is the index of the subtree's first leaf and `count` its number of leaves
+    fn collect_cover(
+        &self,
+        range: &Range<usize>,
+        offset: usize,
+        count: usize,
+        path: &mut Vec<bool>,
+        cover: &mut Vec<(Vec<bool>, Hash)>,
+    ) {
+        if offset >= range.end || range.start >= offset + count {
+            return;
+        }
+        if range.start <= offset && offset + count <= range.end {
+            cover.push((path.clone(), self.hash.clone()));
+            return;
+        }
+
+        if let MerkleTreeChildren::Branch { left, right } = &self.children {
+            let left_count = split_point(count);
+            path.push(false);
+            left.collect_cover(range, offset, left_count, path, cover);
+            path.pop();
+            path.push(true);
+            right.collect_cover(range, offset + left_count, count - left_count, path, cover);
+            path.pop();
+        }
+    }
+
+    /// Collects the roots of subtrees that lie entirely outside the given range
+    /// `offset` is the index of the subtree's first leaf
+    fn collect_outside(&self, range: &Range<usize>, offset: usize, hashes: &mut Vec<Hash>) {
+        match &self.children {
+            MerkleTreeChildren::Branch { left, right } if overlaps(range, offset, self) => {
+                let mid = offset + left.leaf_count();
+                left.collect_outside(range, offset, hashes);
+                right.collect_outside(range, mid, hashes);
+            }
+            MerkleTreeChildren::Leaf { .. } if range.contains(&offset) => {}
+            _ => hashes.push(self.hash.clone()),
+        }
+    }
+}
+
+/// Whether the subtree starting at leaf `offset` has any leaf in the given range
+fn overlaps<D: Digest>(range: &Range<usize>, offset: usize, tree: &MerkleTree<D>) -> bool {
+    offset < range.end && range.start < offset + tree.leaf_count()
+}
+
+impl<D: Digest> RangeProof<D> {
+    /// Verifies that the given leaves are exactly the consecutive leaves from index `start` of
+    /// the tree with the given root hash
+    pub fn verify(&self, leaves: &[Data], start: usize, root: &Hash) -> bool {
+        let end = match start.checked_add(leaves.len()) {
+            Some(end) if !leaves.is_empty() && end <= self.leaf_count => end,
+            _ => return false,
+        };
+
+        let leaves: Vec<_> = leaves.iter().map(hash_data::<D>).collect();
+
+        let mut hashes = self.hashes.iter();
+        match Self::fold(self.leaf_count, 0, &(start..end), &leaves, &mut hashes) {
+            Some(hash) => hashes.next().is_none() && ct_eq(&hash, root),
+            None => false,
+        }
+    }
+
+    /// Recomputes the root of a subtree of `count` leaves starting at leaf `offset`, where
+    /// `leaves` are the hashes of the leaves in `range`
+    fn fold<'a>(
+        count: usize,
+        offset: usize,
+        range: &Range<usize>,
+        leaves: &[Hash],
+        hashes: &mut impl Iterator<Item = &'a Hash>,
+    ) -> Option<Hash> {
+        if offset >= range.end || range.start >= offset + count {
+            return hashes.next().cloned();
+        }
+        if count == 1 {
+            return Some(leaves[offset - range.start].clone());
+        }
+
+        let mid = offset + split_point(count);
+        let left = Self::fold(mid - offset, offset, range, leaves, hashes)?;
+        let right = Self::fold(count - (mid - offset), mid, range, leaves, hashes)?;
+        Some(hash_concat::<D>(&left, &right))
+    }
+}
+
+impl<D> fmt::Debug for RangeProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RangeProof")
+            .field("leaf_count", &self.leaf_count)
+            .field("hashes", &self.hashes)
+            .finish()
+    }
+}
+
+impl<D> Clone for RangeProof<D> {
+    fn clone(&self) -> Self {
+        RangeProof {
+            leaf_count: self.leaf_count,
+            hashes: self.hashes.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialEq for RangeProof<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.leaf_count == other.leaf_count && self.hashes == other.hashes
+    }
+}
+
+impl<D> Eq for RangeProof<D> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::Sha256Tree;
+
+    #[test]
+    fn boundary_siblings_only() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove_range(2, 6).unwrap();
+
+        // Only the pairs of leaves 0-1 and 6-7 are outside the range
+        assert_eq!(
+            proof.hashes,
+            vec![
+                mt.node_hash(&[false, false]).unwrap().clone(),
+                mt.node_hash(&[true, true]).unwrap().clone()
+            ]
+        );
+        assert!(proof.verify(&input[2..6], 2, &mt.hash));
+    }
+
+    #[test]
+    fn non_power_of_two_tree() {
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        for start in 0..7 {
+            for end in start + 1..=7 {
+                let proof = mt.prove_range(start, end).unwrap();
+                assert!(proof.verify(&input[start..end], start, &mt.hash));
+            }
+        }
+    }
+
+    #[test]
+    fn cover() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+
+        assert_eq!(mt.range_cover(0, 8), [(vec![], mt.root().clone())]);
+        assert_eq!(
+            mt.range_cover(2, 6),
+            [
+                (vec![false, true], Sha256Tree::construct(&input[2..4]).hash),
+                (vec![true, false], Sha256Tree::construct(&input[4..6]).hash),
+            ]
+        );
+        assert_eq!(mt.range_cover(1, 8).len(), 3);
+        assert!(mt.range_cover(3, 3).is_empty());
+        assert!(mt.range_cover(0, 9).is_empty());
+
+        // The lone right-most leaf of a 7-leaf tree sits one level above the other leaves
+        let mt = Sha256Tree::construct(&input[..7]);
+        let cover = mt.range_cover(6, 7);
+        assert_eq!(
+            cover,
+            [(vec![true, true], Sha256Tree::construct(&input[6..7]).hash)]
+        );
+    }
+
+    #[test]
+    fn wrong_leaves() {
+        let input: Vec<_> = (0..8).map(|i| vec![i]).collect();
+
+        let mt = Sha256Tree::construct(&input);
+        let proof = mt.prove_range(2, 6).unwrap();
+
+        // Reordered leaves, a shifted start and a shorter range must all fail
+        let mut reordered = input[2..6].to_vec();
+        reordered.swap(1, 2);
+        assert!(!proof.verify(&reordered, 2, &mt.hash));
+        assert!(!proof.verify(&input[2..6], 1, &mt.hash));
+        assert!(!proof.verify(&input[2..5], 2, &mt.hash));
+
+        assert!(mt.prove_range(3, 3).is_none());
+        assert!(mt.prove_range(6, 9).is_none());
+    }
+}
@@ -0,0 +1,219 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
+
+use crate::{check_leaf_count, hash_concat, hash_data, Data, Hash, MerkleError, Proof};
+
+/// A Merkle tree whose identical subtrees are stored only once
+///
+/// Children are reference counted instead of boxed, and construction reuses the node of any
+/// subtree whose hash it has seen before on the same level. Data with many repeated blocks,
+/// such as zero-filled regions, then only takes as many nodes as it has distinct subtrees.
+/// The tree has the same shape and hashes as a [`MerkleTree`](crate::MerkleTree) over the
+/// same input.
+pub struct SharedMerkleTree<D = Sha256> {
+    hash: Hash,
+    /// Left and right subtrees, or `None` for a leaf
+    children: Option<SharedChildren<D>>,
+    digest: PhantomData<fn() -> D>,
+}
+
+/// Left and right subtrees of a shared branch
+type SharedChildren<D> = (Arc<SharedMerkleTree<D>>, Arc<SharedMerkleTree<D>>);
+
+impl<D: Digest> SharedMerkleTree<D> {
+    /// Constructs a tree from given leaf blobs, sharing identical subtrees
+    /// Length of the input must be nonzero
+    pub fn construct(input: &[Data]) -> SharedMerkleTree<D> {
+        Self::try_construct(input).unwrap()
+    }
+
+    /// Constructs a tree from given leaf blobs, failing on empty or oversized input
+    pub fn try_construct(input: &[Data]) -> Result<SharedMerkleTree<D>, MerkleError> {
+        check_leaf_count(input.len())?;
+
+        let mut nodes = BTreeMap::new();
+        let mut level: Vec<_> = input
+            .iter()
+            .map(|item| {
+                let hash = hash_data::<D>(item);
+                Self::intern(&mut nodes, hash, None)
+            })
+            .collect();
+
+        // Pair adjacent nodes level by level, carrying a lone right-most node up unchanged
+        while level.len() > 1 {
+            nodes.clear();
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => {
+                        let hash = hash_concat::<D>(&left.hash, &right.hash);
+                        Self::intern(&mut nodes, hash, Some((left.clone(), right.clone())))
+                    }
+                    [node] => node.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+
+        let root = level.pop().unwrap();
+        Ok(Arc::try_unwrap(root).unwrap_or_else(|root| (*root).clone()))
+    }
+
+    /// Returns the node with the given hash on the current level, creating it if needed
+    fn intern(
+        nodes: &mut BTreeMap<Hash, Arc<SharedMerkleTree<D>>>,
+        hash: Hash,
+        children: Option<SharedChildren<D>>,
+    ) -> Arc<SharedMerkleTree<D>> {
+        nodes
+            .entry(hash)
+            .or_insert_with_key(|hash| {
+                Arc::new(SharedMerkleTree {
+                    hash: hash.clone(),
+                    children,
+                    digest: PhantomData,
+                })
+            })
+            .clone()
+    }
+
+    /// Root hash of the tree
+    pub fn root(&self) -> &Hash {
+        &self.hash
+    }
+
+    /// Number of leaves in this (sub)tree
+    pub fn leaf_count(&self) -> usize {
+        match &self.children {
+            None => 1,
+            // The left subtree is always perfect, so its size follows from its depth
+            Some((left, right)) => (1 << (left.depth() - 1)) + right.leaf_count(),
+        }
+    }
+
+    /// Number of levels from this node down to its deepest leaf, inclusive
+    pub fn depth(&self) -> usize {
+        match &self.children {
+            None => 1,
+            Some((left, _)) => left.depth() + 1,
+        }
+    }
+
+    /// Generates an inclusion proof for the leaf at the given index
+    /// Returns `None` if the index is out of range
+    pub fn prove(&self, index: usize) -> Option<Proof<D>> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+
+        let mut siblings = Vec::new();
+        let mut directions = Vec::new();
+
+        let mut node = self;
+        let mut index = index;
+        while let Some((left, right)) = &node.children {
+            let left_count = left.leaf_count();
+            if index < left_count {
+                siblings.push(right.hash.clone());
+                directions.push(false);
+                node = left;
+            } else {
+                siblings.push(left.hash.clone());
+                directions.push(true);
+                index -= left_count;
+                node = right;
+            }
+        }
+
+        // Proofs are walked from the leaf back to the root
+        siblings.reverse();
+
+        Some(Proof {
+            siblings,
+            directions: directions.into_iter().rev().collect(),
+            digest: PhantomData,
+        })
+    }
+}
+
+impl<D> fmt::Debug for SharedMerkleTree<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedMerkleTree")
+            .field("hash", &self.hash)
+            .field("children", &self.children)
+            .finish()
+    }
+}
+
+/// Cloning shares the children with the original
+impl<D> Clone for SharedMerkleTree<D> {
+    fn clone(&self) -> Self {
+        SharedMerkleTree {
+            hash: self.hash.clone(),
+            children: self.children.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialEq for SharedMerkleTree<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.children == other.children
+    }
+}
+
+impl<D> Eq for SharedMerkleTree<D> {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeSet;
+    use alloc::sync::Arc;
+
+    use super::SharedMerkleTree;
+    use crate::Sha256Tree;
+
+    /// Collects the addresses of all distinct nodes below the given one
+    fn collect_nodes(node: &Arc<SharedMerkleTree>, nodes: &mut BTreeSet<*const SharedMerkleTree>) {
+        if nodes.insert(Arc::as_ptr(node)) {
+            if let Some((left, right)) = &node.children {
+                collect_nodes(left, nodes);
+                collect_nodes(right, nodes);
+            }
+        }
+    }
+
+    #[test]
+    fn identical_subtrees_are_shared() {
+        let input = vec![vec![0; 16]; 8];
+
+        let tree = Arc::new(SharedMerkleTree::construct(&input));
+        assert_eq!(*tree.root(), Sha256Tree::construct(&input).hash);
+
+        // One node per level instead of 15 in total
+        let mut nodes = BTreeSet::new();
+        collect_nodes(&tree, &mut nodes);
+        assert_eq!(nodes.len(), 4);
+    }
+
+    #[test]
+    fn same_tree_as_boxed() {
+        let input: Vec<_> = (0..7).map(|i| vec![i % 2]).collect();
+
+        let tree = SharedMerkleTree::construct(&input);
+        let mt = Sha256Tree::construct(&input);
+        assert_eq!(*tree.root(), mt.hash);
+        assert_eq!(tree.leaf_count(), 7);
+
+        for (i, leaf) in input.iter().enumerate() {
+            assert_eq!(tree.prove(i).unwrap(), mt.prove(i).unwrap());
+            assert!(tree.prove(i).unwrap().verify(leaf, tree.root()));
+        }
+        assert!(tree.prove(7).is_none());
+    }
+}
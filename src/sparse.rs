@@ -0,0 +1,229 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use sha2::{Digest, Sha256};
+
+use crate::{ct_eq, hash_concat, hash_data, Data, EmptyHashes, Hash};
+
+/// An authenticated key-value map over a fixed-depth sparse Merkle tree
+///
+/// Each key is placed at the leaf addressed by the bits of its digest, from the most
+/// significant bit at the root downwards, so the tree is as deep as the digest has bits.
+/// Present leaves hash like regular leaves, while absent leaves and subtrees without any
+/// present leaf take precomputed default hashes, with an all-zero empty leaf at the bottom.
+pub struct SparseMerkleTree<D = Sha256> {
+    /// Values by the digest of their key
+    leaves: BTreeMap<Hash, Data>,
+    /// Hashes of empty subtrees by their height, from a single empty leaf up to the root
+    empty: EmptyHashes<D>,
+    digest: PhantomData<fn() -> D>,
+}
+
+/// A proof that a key is either bound to a value or absent from a sparse Merkle tree
+pub struct SparseProof<D = Sha256> {
+    /// Sibling hashes from the leaf level up to the level just below the root
+    pub(crate) siblings: Vec<Hash>,
+    pub(crate) digest: PhantomData<fn() -> D>,
+}
+
+impl<D: Digest> SparseMerkleTree<D> {
+    /// Creates an empty tree
+    pub fn new() -> SparseMerkleTree<D> {
+        SparseMerkleTree {
+            leaves: BTreeMap::new(),
+            empty: EmptyHashes::new(<D as Digest>::output_size() * 8),
+            digest: PhantomData,
+        }
+    }
+
+    /// Binds a value to the given key, returning the previously bound value
+    pub fn insert(&mut self, key: &[u8], value: Data) -> Option<Data> {
+        self.leaves.insert(D::digest(key).to_vec(), value)
+    }
+
+    /// Value bound to the given key
+    pub fn get(&self, key: &[u8]) -> Option<&Data> {
+        self.leaves.get(D::digest(key).as_slice())
+    }
+
+    /// Root hash of the tree
+    pub fn root(&self) -> Hash {
+        let entries: Vec<_> = self.leaves.iter().collect();
+        self.node_hash(&entries, 0)
+    }
+
+    /// Generates a proof of the value bound to the given key, or of its absence
+    pub fn prove(&self, key: &[u8]) -> SparseProof<D> {
+        let path = D::digest(key).to_vec();
+        let depth = self.empty.max_height();
+
+        let mut siblings = Vec::with_capacity(depth);
+        let mut entries: Vec<_> = self.leaves.iter().collect();
+        for level in 0..depth {
+            let split = entries.partition_point(|(leaf, _)| !path_bit(leaf, level));
+            let right = entries.split_off(split);
+            if path_bit(&path, level) {
+                siblings.push(self.node_hash(&entries, level + 1));
+                entries = right;
+            } else {
+                siblings.push(self.node_hash(&right, level + 1));
+            }
+        }
+        siblings.reverse();
+
+        SparseProof {
+            siblings,
+            digest: PhantomData,
+        }
+    }
+
+    /// Hashes of empty subtrees of every height up to the root, which stand in for the
+    /// subtrees without any present leaf
+    pub fn empty_hashes(&self) -> &EmptyHashes<D> {
+        &self.empty
+    }
+
+    /// Hash of the subtree at the given level holding the given sorted entries
+    fn node_hash(&self, entries: &[(&Hash, &Data)], level: usize) -> Hash {
+        let height = self.empty.max_height() - level;
+        match entries {
+            [] => self.empty.subtree_hash(height).unwrap().clone(),
+            [(_, value)] if height == 0 => hash_data::<D>(value),
+            _ => {
+                let split = entries.partition_point(|(leaf, _)| !path_bit(leaf, level));
+                let (left, right) = entries.split_at(split);
+                hash_concat::<D>(
+                    &self.node_hash(left, level + 1),
+                    &self.node_hash(right, level + 1),
+                )
+            }
+        }
+    }
+}
+
+impl<D: Digest> SparseProof<D> {
+    /// Verifies that the key is bound to the given value, or absent if `value` is `None`,
+    /// in the tree with the given root hash
+    pub fn verify(&self, key: &[u8], value: Option<&Data>, root: &Hash) -> bool {
+        let path = D::digest(key).to_vec();
+        let depth = path.len() * 8;
+        if self.siblings.len() != depth {
+            return false;
+        }
+
+        let mut hash = match value {
+            Some(value) => hash_data::<D>(value),
+            None => alloc::vec![0; path.len()],
+        };
+        for (height, sibling) in self.siblings.iter().enumerate() {
+            hash = if path_bit(&path, depth - 1 - height) {
+                hash_concat::<D>(sibling, &hash)
+            } else {
+                hash_concat::<D>(&hash, sibling)
+            };
+        }
+
+        ct_eq(&hash, root)
+    }
+}
+
+/// Bit of a leaf path at the given level, `true` meaning the right child
+fn path_bit(path: &[u8], level: usize) -> bool {
+    path[level / 8] >> (7 - level % 8) & 1 == 1
+}
+
+impl<D: Digest> Default for SparseMerkleTree<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D> fmt::Debug for SparseMerkleTree<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SparseMerkleTree")
+            .field("leaves", &self.leaves)
+            .finish()
+    }
+}
+
+impl<D> Clone for SparseMerkleTree<D> {
+    fn clone(&self) -> Self {
+        SparseMerkleTree {
+            leaves: self.leaves.clone(),
+            empty: self.empty.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> fmt::Debug for SparseProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SparseProof")
+            .field("siblings", &self.siblings)
+            .finish()
+    }
+}
+
+impl<D> Clone for SparseProof<D> {
+    fn clone(&self) -> Self {
+        SparseProof {
+            siblings: self.siblings.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> PartialEq for SparseProof<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.siblings == other.siblings
+    }
+}
+
+impl<D> Eq for SparseProof<D> {}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::SparseMerkleTree;
+
+    #[test]
+    fn membership_and_absence() {
+        let mut smt = SparseMerkleTree::<Sha256>::new();
+        smt.insert(b"alice", vec![1]);
+        smt.insert(b"bob", vec![2]);
+
+        assert_eq!(smt.get(b"alice"), Some(&vec![1]));
+        assert_eq!(smt.get(b"carol"), None);
+
+        let root = smt.root();
+
+        let proof = smt.prove(b"alice");
+        assert!(proof.verify(b"alice", Some(&vec![1]), &root));
+        assert!(!proof.verify(b"alice", Some(&vec![2]), &root));
+        assert!(!proof.verify(b"alice", None, &root));
+
+        let proof = smt.prove(b"carol");
+        assert!(proof.verify(b"carol", None, &root));
+        assert!(!proof.verify(b"carol", Some(&vec![3]), &root));
+    }
+
+    #[test]
+    fn root_tracks_contents() {
+        let mut smt = SparseMerkleTree::<Sha256>::new();
+        let empty_root = smt.root();
+        assert_eq!(Some(&empty_root), smt.empty.subtree_hash(256));
+
+        smt.insert(b"alice", vec![1]);
+        let root = smt.root();
+        assert_ne!(root, empty_root);
+
+        // Rebinding a key changes the root, restoring it restores the root
+        assert_eq!(smt.insert(b"alice", vec![2]), Some(vec![1]));
+        assert_ne!(smt.root(), root);
+        smt.insert(b"alice", vec![1]);
+        assert_eq!(smt.root(), root);
+    }
+}
@@ -0,0 +1,436 @@
+//! Deserializes trees with a bound on their depth, so that adversarial input cannot overflow
+//! the stack by nesting branches arbitrarily deep
+//!
+//! The format is the one the `Serialize` implementations write, derived for trees and written
+//! out here for their children.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::de::{
+    DeserializeSeed, Deserializer, EnumAccess, Error, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use serde::ser::SerializeStructVariant;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{hex_serde, HashScheme, MerkleTree, MerkleTreeChildren, MAX_DEPTH};
+
+/// Leaves are written as unit variants, leaving out any retained data
+impl<D> Serialize for MerkleTreeChildren<D> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MerkleTreeChildren::Leaf { .. } => {
+                serializer.serialize_unit_variant("MerkleTreeChildren", 0, "Leaf")
+            }
+            MerkleTreeChildren::Branch { left, right } => {
+                let mut branch =
+                    serializer.serialize_struct_variant("MerkleTreeChildren", 1, "Branch", 2)?;
+                branch.serialize_field("left", left)?;
+                branch.serialize_field("right", right)?;
+                branch.end()
+            }
+        }
+    }
+}
+
+impl<'de, D> Deserialize<'de> for MerkleTree<D> {
+    fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        let (tree, _) = NodeSeed::at_depth(1).deserialize(deserializer)?;
+        Ok(tree)
+    }
+}
+
+impl<'de, D> Deserialize<'de> for MerkleTreeChildren<D> {
+    fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        let (children, _) = ChildrenSeed::at_depth(1).deserialize(deserializer)?;
+        Ok(children)
+    }
+}
+
+/// Depth of a deserialized subtree and whether all its leaves are at that depth
+///
+/// Leaf counts, proofs and the flat layout rely on the shape [`MerkleTree::construct`] gives
+/// every tree, so branches whose left subtree is not perfect or is shallower than the right
+/// one are rejected as they are read.
+#[derive(Clone, Copy)]
+struct Shape {
+    depth: usize,
+    perfect: bool,
+}
+
+impl Shape {
+    const LEAF: Shape = Shape {
+        depth: 1,
+        perfect: true,
+    };
+
+    /// Shape of a branch over subtrees of the given shapes, if it is one `construct` builds
+    fn branch(left: Shape, right: Shape) -> Option<Shape> {
+        (left.perfect && right.depth <= left.depth).then_some(Shape {
+            depth: left.depth + 1,
+            perfect: right.perfect && right.depth == left.depth,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum NodeField {
+    Hash,
+    Children,
+    Scheme,
+}
+
+#[derive(Deserialize)]
+#[serde(variant_identifier)]
+enum ChildrenVariant {
+    Leaf,
+    Branch,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum BranchField {
+    Left,
+    Right,
+}
+
+/// Deserializes a node at the given depth, counting the root as depth 1
+struct NodeSeed<D> {
+    depth: usize,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> NodeSeed<D> {
+    fn at_depth(depth: usize) -> NodeSeed<D> {
+        NodeSeed {
+            depth,
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<'de, D> DeserializeSeed<'de> for NodeSeed<D> {
+    type Value = (MerkleTree<D>, Shape);
+
+    fn deserialize<De: Deserializer<'de>>(
+        self,
+        deserializer: De,
+    ) -> Result<Self::Value, De::Error> {
+        // Fail before descending any further
+        if self.depth > MAX_DEPTH {
+            return Err(De::Error::custom(format_args!(
+                "tree is deeper than the maximum of {MAX_DEPTH} levels"
+            )));
+        }
+        deserializer.deserialize_struct("MerkleTree", &["hash", "children", "scheme"], self)
+    }
+}
+
+/// Assembles a deserialized node, whose children must share its hashing scheme
+fn node<D, E: Error>(
+    hash: Vec<u8>,
+    (children, shape): (MerkleTreeChildren<D>, Shape),
+    scheme: Option<HashScheme>,
+) -> Result<(MerkleTree<D>, Shape), E> {
+    let scheme = scheme.unwrap_or_default();
+    if let MerkleTreeChildren::Branch { left, right } = &children {
+        if left.scheme != scheme || right.scheme != scheme {
+            return Err(E::custom("branch has children of another hashing scheme"));
+        }
+    }
+
+    let tree = MerkleTree {
+        hash,
+        children,
+        scheme,
+        digest: PhantomData,
+    };
+    Ok((tree, shape))
+}
+
+impl<'de, D> Visitor<'de> for NodeSeed<D> {
+    type Value = (MerkleTree<D>, Shape);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a Merkle tree node")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let hash = seq
+            .next_element_seed(HashSeed)?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let children = seq
+            .next_element_seed(ChildrenSeed::at_depth(self.depth))?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        // Only trees of other schemes than the standard one write it
+        let scheme = seq.next_element()?;
+
+        node(hash, children, scheme)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut hash = None;
+        let mut children = None;
+        let mut scheme = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                NodeField::Hash if hash.is_none() => hash = Some(map.next_value_seed(HashSeed)?),
+                NodeField::Children if children.is_none() => {
+                    children = Some(map.next_value_seed(ChildrenSeed::at_depth(self.depth))?)
+                }
+                NodeField::Scheme if scheme.is_none() => scheme = Some(map.next_value()?),
+                NodeField::Hash => return Err(A::Error::duplicate_field("hash")),
+                NodeField::Children => return Err(A::Error::duplicate_field("children")),
+                NodeField::Scheme => return Err(A::Error::duplicate_field("scheme")),
+            }
+        }
+
+        node(
+            hash.ok_or_else(|| A::Error::missing_field("hash"))?,
+            children.ok_or_else(|| A::Error::missing_field("children"))?,
+            scheme,
+        )
+    }
+}
+
+/// Deserializes the children of a node at the given depth
+struct ChildrenSeed<D> {
+    depth: usize,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> ChildrenSeed<D> {
+    fn at_depth(depth: usize) -> ChildrenSeed<D> {
+        ChildrenSeed {
+            depth,
+            digest: PhantomData,
+        }
+    }
+
+    fn child(&self) -> NodeSeed<D> {
+        NodeSeed::at_depth(self.depth + 1)
+    }
+}
+
+impl<'de, D> DeserializeSeed<'de> for ChildrenSeed<D> {
+    type Value = (MerkleTreeChildren<D>, Shape);
+
+    fn deserialize<De: Deserializer<'de>>(
+        self,
+        deserializer: De,
+    ) -> Result<Self::Value, De::Error> {
+        deserializer.deserialize_enum("MerkleTreeChildren", &["Leaf", "Branch"], self)
+    }
+}
+
+impl<'de, D> Visitor<'de> for ChildrenSeed<D> {
+    type Value = (MerkleTreeChildren<D>, Shape);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the children of a Merkle tree node")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        match data.variant()? {
+            (ChildrenVariant::Leaf, variant) => {
+                variant.unit_variant()?;
+                Ok((MerkleTreeChildren::Leaf { data: None }, Shape::LEAF))
+            }
+            (ChildrenVariant::Branch, variant) => {
+                variant.struct_variant(&["left", "right"], BranchVisitor(self))
+            }
+        }
+    }
+}
+
+/// Deserializes the fields of a branch
+struct BranchVisitor<D>(ChildrenSeed<D>);
+
+impl<'de, D> Visitor<'de> for BranchVisitor<D> {
+    type Value = (MerkleTreeChildren<D>, Shape);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a Merkle tree branch")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let left = seq
+            .next_element_seed(self.0.child())?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let right = seq
+            .next_element_seed(self.0.child())?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+
+        branch(left, right)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut left = None;
+        let mut right = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                BranchField::Left if left.is_none() => {
+                    left = Some(map.next_value_seed(self.0.child())?)
+                }
+                BranchField::Right if right.is_none() => {
+                    right = Some(map.next_value_seed(self.0.child())?)
+                }
+                BranchField::Left => return Err(A::Error::duplicate_field("left")),
+                BranchField::Right => return Err(A::Error::duplicate_field("right")),
+            }
+        }
+
+        branch(
+            left.ok_or_else(|| A::Error::missing_field("left"))?,
+            right.ok_or_else(|| A::Error::missing_field("right"))?,
+        )
+    }
+}
+
+/// Assembles a deserialized branch, which must have the shape `construct` would give it
+fn branch<D, E: Error>(
+    (left, left_shape): (MerkleTree<D>, Shape),
+    (right, right_shape): (MerkleTree<D>, Shape),
+) -> Result<(MerkleTreeChildren<D>, Shape), E> {
+    let shape = Shape::branch(left_shape, right_shape).ok_or_else(|| {
+        E::custom("branch does not have a perfect left subtree at least as deep as the right one")
+    })?;
+
+    let children = MerkleTreeChildren::Branch {
+        left: Box::new(left),
+        right: Box::new(right),
+    };
+    Ok((children, shape))
+}
+
+/// Deserializes a hash as written by [`hex_serde`]
+struct HashSeed;
+
+impl<'de> DeserializeSeed<'de> for HashSeed {
+    type Value = Vec<u8>;
+
+    fn deserialize<De: Deserializer<'de>>(
+        self,
+        deserializer: De,
+    ) -> Result<Self::Value, De::Error> {
+        hex_serde::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Sha256Tree, MAX_DEPTH};
+
+    /// JSON of a branch over the given subtrees
+    fn branch_json(left: &str, right: &str) -> String {
+        let hash = "00".repeat(32);
+        format!(r#"{{"hash":"{hash}","children":{{"Branch":{{"left":{left},"right":{right}}}}}}}"#)
+    }
+
+    fn leaf_json() -> String {
+        let hash = "00".repeat(32);
+        format!(r#"{{"hash":"{hash}","children":"Leaf"}}"#)
+    }
+
+    /// JSON of a perfect tree `depth` levels deep
+    fn perfect_json(depth: usize) -> String {
+        let mut json = leaf_json();
+        for _ in 1..depth {
+            json = branch_json(&json, &json);
+        }
+        json
+    }
+
+    /// JSON of the shallowest tree `construct` builds `depth` levels deep: a perfect left
+    /// subtree and a single leaf on the right
+    fn chain_json(depth: usize) -> String {
+        match depth {
+            1 => leaf_json(),
+            _ => branch_json(&perfect_json(depth - 1), &leaf_json()),
+        }
+    }
+
+    /// JSON of a chain of branches `depth` levels deep, each with a leaf on the left, which
+    /// no tree has
+    fn right_chain_json(depth: usize) -> String {
+        let mut json = leaf_json();
+        for _ in 1..depth {
+            json = branch_json(&leaf_json(), &json);
+        }
+        json
+    }
+
+    fn from_json(json: &str) -> Result<Sha256Tree, serde_json::Error> {
+        // Lift the parser's own nesting limit, which is lower than the tree depth limit
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        deserializer.disable_recursion_limit();
+        serde::Deserialize::deserialize(&mut deserializer)
+    }
+
+    #[test]
+    fn depth_limit() {
+        // Nesting is bounded before the shape of the branches is checked on the way back up
+        let error = from_json(&right_chain_json(MAX_DEPTH + 1)).unwrap_err();
+        assert!(error.to_string().contains("deeper than the maximum"));
+        let error = from_json(&right_chain_json(MAX_DEPTH)).unwrap_err();
+        assert!(error.to_string().contains("perfect left subtree"));
+    }
+
+    #[test]
+    fn canonical_shapes() {
+        let tree = from_json(&chain_json(10)).unwrap();
+        assert_eq!(tree.depth(), 10);
+        assert_eq!(tree.leaf_count(), 257);
+        assert_eq!(tree.prove(256).unwrap().siblings().len(), 1);
+
+        let input: Vec<_> = (0..7).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct(&input);
+        let tree = from_json(&serde_json::to_string(&mt).unwrap()).unwrap();
+        assert_eq!(tree, mt);
+
+        // A left subtree that is not perfect, or a right one deeper than the left
+        let lopsided = branch_json(&chain_json(3), &leaf_json());
+        assert!(from_json(&lopsided).is_err());
+        let deeper = branch_json(&leaf_json(), &perfect_json(2));
+        assert!(from_json(&deeper).is_err());
+    }
+
+    #[test]
+    fn scheme_round_trip() {
+        let input: Vec<_> = (0..5).map(|i| vec![i]).collect();
+        let mt = Sha256Tree::construct_indexed(&input);
+
+        let json = serde_json::to_string(&mt).unwrap();
+        let mut tree = from_json(&json).unwrap();
+        assert_eq!(tree, mt);
+        assert!(tree.validate());
+        tree.update(0, vec![9]).unwrap();
+        assert!(tree.validate());
+
+        // Trees of the standard scheme do not write it
+        let json = serde_json::to_string(&Sha256Tree::construct(&input)).unwrap();
+        assert!(!json.contains("scheme"));
+
+        let hash = "00".repeat(32);
+        let leaf = format!(r#"{{"hash":"{hash}","children":"Leaf"}}"#);
+        let mixed = format!(
+            r#"{{"hash":"{hash}","children":{{"Branch":{{"left":{leaf},"right":{leaf}}}}},"scheme":"Indexed"}}"#
+        );
+        assert!(from_json(&mixed).is_err());
+    }
+
+    #[test]
+    fn malformed_nodes() {
+        let hash = "00".repeat(32);
+        assert!(from_json(&format!(r#"{{"hash":"{hash}"}}"#)).is_err());
+        assert!(from_json(r#"{"children":"Leaf"}"#).is_err());
+        assert!(from_json(&format!(r#"{{"hash":"{hash}","children":"Tree"}}"#)).is_err());
+        assert!(from_json(&format!(
+            r#"{{"hash":"{hash}","hash":"{hash}","children":"Leaf"}}"#
+        ))
+        .is_err());
+    }
+}